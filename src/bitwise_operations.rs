@@ -0,0 +1,8 @@
+use crate::calc::CalculationError;
+pub trait BitwiseOperations {
+    fn bit_and(&mut self, value: f64) -> Result<(), CalculationError>;
+    fn bit_or(&mut self, value: f64) -> Result<(), CalculationError>;
+    fn bit_xor(&mut self, value: f64) -> Result<(), CalculationError>;
+    fn shift_left(&mut self, value: f64) -> Result<(), CalculationError>;
+    fn shift_right(&mut self, value: f64) -> Result<(), CalculationError>;
+}
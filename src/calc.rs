@@ -1,116 +1,1905 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::IsTerminal;
 use std::num::{ParseFloatError, ParseIntError};
-use std::rc::{Rc, Weak};
-use std::cell::RefCell;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::path::Path;
+use crate::bitwise_operations::BitwiseOperations;
+use crate::conversions;
 use crate::general_operations::GeneralOperations;
 use crate::logic_operations::LogicOperations;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "decimal")]
+use rust_decimal::{Decimal, prelude::ToPrimitive};
 
 fn get_input<T>() -> Result<T, CalculationError>  where T: std::str::FromStr, T::Err: std::fmt::Display, {
     let mut input = String::new();
     match std::io::stdin().read_line(&mut input) {
-        Ok(_) => { let input = input.trim(); if input.is_empty() {
-                Err(CalculationError::ParseError("Empty input".to_string()))
-            } else { match input.parse::<T>() {
-                    Ok(val) => Ok(val),
-                    Err(e) => Err(CalculationError::ParseError(format!("Parse error: {}", e))), }
+        Ok(0) => Err(CalculationError::EndOfInput),
+        Ok(_) => parse_input(input.trim()),
+        Err(e) => Err(CalculationError::ParseError(format!("IO error: {}", e))), }
+}
+
+fn parse_input<T>(input: &str) -> Result<T, CalculationError>  where T: std::str::FromStr, T::Err: std::fmt::Display, {
+    if input.is_empty() {
+        return Err(CalculationError::ParseError("Empty input".to_string()));
+    }
+    input.parse::<T>().map_err(|e| CalculationError::ParseError(format!("Parse error: {}", e)))
+}
+
+// Numbered lines that REPL commands and their prompts read from: stdin for the interactive
+// session, or a script for run_script, which prints no prompts and reports failures on stderr
+struct CommandInput<I> {
+    lines: I,
+    script: bool,
+    line_number: usize,
+    // Value typed on the same line as its command, served before reading another line
+    inline: Option<String>,
+    // Set once a read finds no more lines, so the session ends instead of prompting again
+    ended: bool,
+}
+
+impl CommandInput<()> {
+    fn stdin() -> CommandInput<impl Iterator<Item = (usize, String)>> {
+        let lines = std::iter::from_fn(|| {
+            let mut line = String::new();
+            match std::io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => None,
+                Ok(_) => Some((0, line.trim().to_string())),
             }
+        });
+        CommandInput { lines, script: false, line_number: 0, inline: None, ended: false }
+    }
+}
+
+impl<I: Iterator<Item = (usize, String)>> CommandInput<I> {
+    fn next_line(&mut self) -> Result<String, CalculationError> {
+        if let Some(inline) = self.inline.take() {
+            return Ok(inline);
         }
-        Err(e) => Err(CalculationError::ParseError(format!("IO error: {}", e))), }
+        let Some((number, line)) = self.lines.next() else {
+            self.ended = true;
+            return Err(CalculationError::EndOfInput);
+        };
+        self.line_number = number;
+        Ok(line)
+    }
+
+    fn read<T>(&mut self) -> Result<T, CalculationError> where T: std::str::FromStr, T::Err: std::fmt::Display, {
+        parse_input(&self.next_line()?)
+    }
+
+    // A value for an operation, in any form parse_number accepts
+    fn read_value(&mut self, verbose: bool) -> Result<f64, CalculationError> {
+        self.prompt(verbose, "Enter value:");
+        parse_number(&self.next_line()?)
+    }
+
+    // A count and then that many numbers, one per line
+    fn read_values(&mut self, verbose: bool) -> Result<Vec<f64>, CalculationError> {
+        self.prompt(verbose, "How many values?");
+        let count = self.read::<usize>()?;
+        (1..=count).map(|i| { self.prompt(verbose, &format!("Value {}:", i)); self.read::<f64>() }).collect()
+    }
+
+    // Quiet sessions and scripts read input without a prompt
+    fn prompt(&self, verbose: bool, text: &str) {
+        if verbose && !self.script { println!("{}", text); }
+    }
+
+    fn report(&self, message: &str) {
+        // A REPL prompt cut short by end of input is not a failure; the session just ends
+        if self.ended && !self.script {
+            return;
+        }
+        if self.script {
+            eprintln!("Line {}: {}", self.line_number, message);
+        } else {
+            println!("{}", error_text(message));
+        }
+    }
+
+    // Centralized error reporting for operations
+    fn handle(&self, result: Result<(), CalculationError>, operation: &str) {
+        match result {
+            Ok(()) => {}
+            Err(e @ CalculationError::Overflow) => self.report(&format!("{} failed: {} (result too large to represent). State preserved.", operation, e)),
+            Err(e @ CalculationError::PrecisionLoss) => self.report(&format!("{} failed: {} (too many significant digits). State preserved.", operation, e)),
+            Err(e) => self.report(&format!("{} failed: {}. State preserved.", operation, e)),
+        }
+    }
+}
+
+// Wrap text in an ANSI SGR code; plain text unless the color feature is on and stdout is a terminal
+fn paint(text: &str, code: &str) -> String {
+    if cfg!(feature = "color") && std::io::stdout().is_terminal() {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+// Bold, for the current value in the prompt
+fn highlight(text: &str) -> String {
+    paint(text, "1")
+}
+
+// Red, for failure messages
+fn error_text(text: &str) -> String {
+    paint(text, "31")
+}
+
+// Parse a typed value, accepting 0x/0b/0o prefixed integer literals alongside plain decimals
+// and scientific notation such as 1.5e3
+fn parse_number(s: &str) -> Result<f64, CalculationError> {
+    let s = s.trim();
+    let (negative, body) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let radix = match body.get(..2).map(str::to_ascii_lowercase).as_deref() {
+        Some("0x") => Some(16),
+        Some("0b") => Some(2),
+        Some("0o") => Some(8),
+        _ => None,
+    };
+    let magnitude = match radix {
+        Some(radix) => i64::from_str_radix(&body[2..], radix)
+            .map(|v| v as f64)
+            .map_err(|e| CalculationError::ParseError(format!("Invalid literal '{}': {}", s, e)))?,
+        None => {
+            validate_decimal(body, s)?;
+            body.parse::<f64>().map_err(|e| CalculationError::ParseError(format!("Invalid number '{}': {}", s, e)))?
+        }
+    };
+    Ok(if negative { -magnitude } else { magnitude })
+}
+
+// Check a decimal token against digits[.digits][e[+-]digits], so strings such as "1e",
+// "inf" or "1.2.3" are rejected with a message naming the token
+fn validate_decimal(body: &str, token: &str) -> Result<(), CalculationError> {
+    let (mantissa, exponent) = match body.find(['e', 'E']) {
+        Some(pos) => (&body[..pos], Some(&body[pos + 1..])),
+        None => (body, None),
+    };
+    let mut parts = mantissa.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !all_digits(whole) || !all_digits(fraction) {
+        return Err(CalculationError::ParseError(format!("Invalid number '{}'", token)));
+    }
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if digits.is_empty() {
+            return Err(CalculationError::ParseError(format!("Missing exponent digits in '{}'", token)));
+        }
+        if !all_digits(digits) {
+            return Err(CalculationError::ParseError(format!("Invalid exponent in '{}'", token)));
+        }
+    }
+    Ok(())
+}
+
+// Split an infix expression into numbers, names and single-character symbols
+fn tokenize_expression(expr: &str) -> Result<Vec<String>, CalculationError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' || c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+                // Keep the sign of a decimal exponent, as in 2e-3
+                let decimal = c.is_ascii_digit() && !matches!(chars.get(start + 1), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O'));
+                if decimal && matches!(chars[i - 1], 'e' | 'E') && matches!(chars.get(i), Some('+' | '-')) {
+                    i += 1;
+                }
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else if "+-*/^()".contains(c) {
+            tokens.push(c.to_string());
+            i += 1;
+        } else {
+            return Err(CalculationError::ParseError(format!("Unexpected character '{}' in expression", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive-descent parser over expression tokens; ^ binds tighter than unary minus
+// and is right-associative, so -2^2 is -4 and 2^3^2 is 512
+struct ExpressionParser<'a> {
+    tokens: Vec<String>,
+    pos: usize,
+    variables: &'a HashMap<String, f64>,
+}
+
+impl ExpressionParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn expression(&mut self) -> Result<f64, CalculationError> {
+        let mut value = self.term()?;
+        while let Some(op @ ("+" | "-")) = self.peek() {
+            let add = op == "+";
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if add { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, CalculationError> {
+        let mut value = self.unary()?;
+        while let Some(op @ ("*" | "/")) = self.peek() {
+            let multiply = op == "*";
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = if multiply {
+                value * rhs
+            } else if rhs == 0.0 {
+                return Err(CalculationError::DivisionByZero);
+            } else {
+                value / rhs
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64, CalculationError> {
+        if self.peek() == Some("-") {
+            self.pos += 1;
+            return Ok(-self.unary()?);
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<f64, CalculationError> {
+        let base = self.primary()?;
+        if self.peek() == Some("^") {
+            self.pos += 1;
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<f64, CalculationError> {
+        let token = self.tokens.get(self.pos).cloned()
+            .ok_or_else(|| CalculationError::ParseError("Unexpected end of expression".to_string()))?;
+        self.pos += 1;
+        match token.as_str() {
+            "(" => {
+                let value = self.expression()?;
+                if self.peek() != Some(")") {
+                    return Err(CalculationError::ParseError("Missing ')' in expression".to_string()));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            t if t.starts_with(|c: char| c.is_ascii_digit() || c == '.') => parse_number(t),
+            t if t.starts_with(|c: char| c.is_alphabetic() || c == '_') => self.variables.get(t).copied()
+                .ok_or_else(|| CalculationError::ParseError(format!("Undefined variable '{}'", t))),
+            t => Err(CalculationError::ParseError(format!("Unexpected '{}' in expression", t))),
+        }
+    }
 }
 
-// Complete snapshot of calculator state for proper recovery
+// Largest denominator the REPL's fraction command and fraction display will show
+const FRACTION_MAX_DENOMINATOR: u64 = 1000;
+
+// How close p/q must come to a value for the fraction display to show it alongside
+const FRACTION_DISPLAY_TOLERANCE: f64 = 1e-9;
+
+// Longest continued fraction expansion worth computing from an f64
+const CONTINUED_FRACTION_MAX_TERMS: usize = 64;
+
+// Tallest power tower tetrate will evaluate
+const TETRATION_MAX_HEIGHT: u32 = 10_000;
+
+// Number of indexed memory registers
+const MEMORY_SLOTS: usize = 10;
+
+// Tolerance used by the REPL when undoing back to a typed-in value
+const UNDO_UNTIL_EPSILON: f64 = 1e-9;
+
+// Complete snapshot of calculator state for proper recovery.
+// Nodes live in the calculator's arena, so only indices need to be stored, and the
+// history path is shared with the calculator until one side modifies it.
 #[derive(Clone)]
 struct CalculatorSnapshot {
-    root: Rc<RefCell<Node>>,
-    current: Rc<RefCell<Node>>,
-    history: Vec<Rc<RefCell<Node>>>,
+    root: usize,
+    current: usize,
+    history: Rc<Vec<usize>>,
     history_index: usize,
 }
 
+// One node's (value, label, operand, extra) when rebuilding the tree as a chain
+type ChainStep = (f64, Option<String>, Option<f64>, Option<f64>);
+
+// Tree node stored in the calculator's arena; parent and children are arena indices
 pub struct Node {
     value: f64,
-    parent: Option<Weak<RefCell<Node>>>,
-    child_item: Vec<Rc<RefCell<Node>>>,
+    parent: Option<usize>,
+    child_item: Vec<usize>,
+    last_op: Option<String>,
+    operand: Option<f64>,  // Input value of last_op, kept so the step can be replayed
+    extra: Option<f64>,    // Second input of two-input steps, e.g. the periods of "compound"
+}
+
+impl Node {
+    fn new(value: f64, parent: Option<usize>, op: Option<String>, operand: Option<f64>) -> Self {
+        Self { value, last_op: op, operand, extra: None, parent, child_item: Vec::new(), }
+    }
+
+    // Convenience method for root nodes (maintains existing API)
+    fn new_root(value: f64) -> Self {
+        Self::new(value, None, None, None)
+    }
+}
+
+// Serializable node; `parent` is the pre-order position of its parent, so it always precedes the node.
+// A flat list keeps long sessions clear of serde_json's nesting limit.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SerializedNode {
+    value: f64,
     last_op: Option<String>,
+    #[serde(default)]
+    operand: Option<f64>,
+    #[serde(default)]
+    extra: Option<f64>,
+    parent: Option<usize>,
 }
 
-impl Node {
-    fn new(value: f64, parent: Option<&Rc<RefCell<Node>>>, op: Option<String>) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Self { value, last_op: op, parent: parent.map(|p| Rc::downgrade(p)), child_item: Vec::new(), }))
+// Serializable session: `current` and `history` are pre-order positions within `nodes`
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SerializedSession {
+    nodes: Vec<SerializedNode>,
+    current: usize,
+    history: Vec<usize>,
+    history_index: usize,
+}
+
+// Incremental mean/variance over every value inserted this session (Welford's algorithm)
+#[derive(Default)]
+struct RunningStats {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningStats {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    // Population variance
+    fn variance(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.m2 / self.count as f64 }
+    }
+}
+
+// Summary of the values along the history path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryStats {
+    pub count: usize,
+    pub sum: f64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+// Arithmetic mode: Integer truncates every result toward zero and divides with floor quotients.
+// Decimal (feature "decimal") does + - * / in base-10 arithmetic, so 0.1 + 0.2 is exactly 0.3.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalcMode {
+    Real,
+    Integer,
+    #[cfg(feature = "decimal")]
+    Decimal,
+}
+
+// Chain applies each operation as soon as it is entered; Immediate holds a binary operator and
+// its operand as pending until the next operator commits them, like a desk calculator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryMode {
+    Chain,
+    Immediate,
+}
+
+// Unit used for angles produced by trigonometric operations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AngleMode {
+    Radians,
+    Degrees,
+}
+
+// How values are rendered by show/output and the REPL
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Decimal,
+    Scientific,   // 1.234e3
+    Engineering,  // Exponent rounded down to a multiple of 3, e.g. 12.345e3
+}
+
+// How the round operation resolves fractions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundingMode {
+    HalfUp,        // Ties toward +infinity: 2.5 -> 3, -2.5 -> -2
+    HalfEven,      // Ties to the even neighbour: 2.5 -> 2, 3.5 -> 4
+    TowardZero,
+    AwayFromZero,
+}
+
+impl RoundingMode {
+    fn round(self, value: f64) -> f64 {
+        match self {
+            RoundingMode::HalfUp => {
+                let floor = value.floor();
+                if value - floor >= 0.5 { floor + 1.0 } else { floor }
+            }
+            RoundingMode::HalfEven => value.round_ties_even(),
+            RoundingMode::TowardZero => value.trunc(),
+            RoundingMode::AwayFromZero => if value < 0.0 { value.floor() } else { value.ceil() },
+        }
+    }
+
+    // Round to the nearest integer using only this mode's tie rule; the directed modes fall
+    // back to f64::round so display snapping still picks the closest candidate
+    fn nearest(self, value: f64) -> f64 {
+        match self {
+            RoundingMode::HalfUp | RoundingMode::HalfEven => self.round(value),
+            RoundingMode::TowardZero | RoundingMode::AwayFromZero => value.round(),
+        }
+    }
+}
+
+pub struct RustyCalculator {
+    nodes: Vec<Node>,                    // Arena owning every node ever created
+    root: usize,
+    current: usize,
+    history: Rc<Vec<usize>>,            // Copy-on-write, shared with snapshots
+    history_index: usize,
+    snapshots: Vec<CalculatorSnapshot>,  // Store complete calculator states
+    named_snapshots: HashMap<String, CalculatorSnapshot>,  // Restore points kept apart from the LIFO stack
+    custom_ops: HashMap<String, Rc<dyn Fn(f64) -> f64>>,  // User-registered operations by name
+    output_format: OutputFormat,
+    group_digits: bool,                  // Thousands separators in decimal output
+    fraction_display: bool,              // Append p/q to values a small fraction reproduces
+    running_stats: RunningStats,
+    calc_mode: CalcMode,
+    variables: HashMap<String, f64>,     // Named registers, kept across resets
+    transcript: Vec<String>,             // Chronological log of every attempted operation
+    accumulator: f64,                    // Running total kept outside the node tree
+    max_significant_digits: i32,         // Integer digits allowed before PrecisionLoss
+    round_display_epsilon: Option<f64>,  // Display-only snapping of float noise, e.g. 0.30000000000000004
+    angle_mode: AngleMode,
+    rounding_mode: RoundingMode,
+    memory: [f64; MEMORY_SLOTS],         // Indexed memory registers; slot 0 is the default register
+    max_history: Option<usize>,          // Undo depth limit; older history entries are dropped
+    equals_epsilon: f64,                 // Tolerance used by the == comparison
+    entry_mode: EntryMode,
+    pending: Option<(String, Option<f64>)>,  // Operator label and operand awaiting commit
+    last_operation: Option<(String, Option<f64>, Option<f64>)>,  // Most recent successful operation (label, operand, extra), for repeat_last
+    verbose: bool,                       // REPL banners and prompts; quiet mode prints only results and errors
+}
+
+impl RustyCalculator {
+    pub fn new(rest_state: f64) -> RustyCalculator {
+        let mut running_stats = RunningStats::default();
+        running_stats.push(rest_state);
+        Self {
+            nodes: vec![Node::new_root(rest_state)],
+            root: 0,
+            current: 0,
+            history: Rc::new(vec![0]),
+            history_index: 0,
+            snapshots: Vec::new(),
+            named_snapshots: HashMap::new(),
+            custom_ops: HashMap::new(),
+            output_format: OutputFormat::Decimal,
+            group_digits: false,
+            fraction_display: false,
+            running_stats,
+            calc_mode: CalcMode::Real,
+            variables: HashMap::new(),
+            transcript: Vec::new(),
+            accumulator: 0.0,
+            max_significant_digits: 15,
+            round_display_epsilon: None,
+            angle_mode: AngleMode::Radians,
+            rounding_mode: RoundingMode::HalfEven,
+            memory: [0.0; MEMORY_SLOTS],
+            max_history: None,
+            equals_epsilon: 1e-9,
+            entry_mode: EntryMode::Chain,
+            verbose: true,
+            pending: None,
+            last_operation: None,
+        }
+    }
+
+    // Move a node into the arena and return its index
+    fn push_node(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn insert_node(&mut self, value: f64, op: Option<String>, operand: Option<f64>) -> usize {
+        let new_node = self.push_node(Node::new(value, Some(self.current), op, operand));
+        self.nodes[self.current].child_item.push(new_node);
+
+        // When creating new nodes, truncate history after current position and add new node
+        let history = Rc::make_mut(&mut self.history);
+        history.truncate(self.history_index + 1);
+        history.push(new_node);
+        self.history_index = self.history.len() - 1; // always point to last node
+        self.current = new_node;
+        self.running_stats.push(value);
+        self.trim_history();
+
+        new_node
+    }
+
+    // Drop the oldest history entries beyond max_history, never past the current position.
+    // Dropped nodes stay in the tree; they are just no longer reachable by undo.
+    fn trim_history(&mut self) {
+        let Some(max) = self.max_history else { return };
+        let excess = self.history.len().saturating_sub(max.max(1)).min(self.history_index);
+        if excess > 0 {
+            Rc::make_mut(&mut self.history).drain(..excess);
+            self.history_index -= excess;
+        }
+    }
+
+    // Nodes from root down to current, following parent links
+    fn path_to_current(&self) -> Vec<usize> {
+        let mut path = vec![self.current];
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            path.push(parent);
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    // Replace the tree with a fresh linear chain of (value, label, operand, extra) steps. Old nodes stay
+    // in the arena untouched, so snapshots taken beforehand still recover the original tree.
+    fn rebuild_as_chain(&mut self, steps: Vec<ChainStep>) {
+        let mut history = Vec::with_capacity(steps.len());
+        let mut parent = None;
+        for (value, op, operand, extra) in steps {
+            let node = self.push_node(Node::new(value, parent, op, operand));
+            self.nodes[node].extra = extra;
+            if let Some(parent) = parent {
+                self.nodes[parent].child_item.push(node);
+            }
+            history.push(node);
+            parent = Some(node);
+        }
+        self.root = history[0];
+        self.current = history[history.len() - 1];
+        self.history_index = history.len() - 1;
+        self.history = Rc::new(history);
+    }
+
+    // Apply operation with automatic last_op tracking.
+    // The result is validated before a node is created, so failed operations leave no trace.
+    fn apply_op<F>(&mut self, op_fn: F, op_label: &str, operand: Option<f64>) -> Result<(), CalculationError>
+    where F: FnOnce(f64) -> f64, {
+        self.apply_op_with_extra(op_fn, op_label, operand, None)
+    }
+
+    // apply_op for steps with a second input, which is stored on the node so the step can be replayed
+    fn apply_op_with_extra<F>(&mut self, op_fn: F, op_label: &str, operand: Option<f64>, extra: Option<f64>) -> Result<(), CalculationError>
+    where F: FnOnce(f64) -> f64, {
+        let prev = self.nodes[self.current].value;
+        let candidate = match self.calc_mode {
+            CalcMode::Real => op_fn(prev),
+            CalcMode::Integer => op_fn(prev).trunc(),
+            #[cfg(feature = "decimal")]
+            CalcMode::Decimal => Self::decimal_step(op_label, prev, operand).unwrap_or_else(|| op_fn(prev)),
+        };
+        let valid = match self.checked_value(prev, candidate) {
+            Ok(valid) => valid,
+            Err(e) => return self.reject(op_label, operand, e),
+        };
+        let node = self.insert_node(valid, Some(op_label.to_string()), operand);
+        self.nodes[node].extra = extra;
+        self.log_operation(op_label, operand, &valid.to_string());
+        self.last_operation = Some((op_label.to_string(), operand, extra));
+        Ok(())
+    }
+
+    // apply_op for steps apply_labelled cannot re-run because their inputs are not stored (closures,
+    // value lists, snapshot blends). repeat_last keeps pointing at the previous repeatable operation.
+    fn apply_unrepeatable<F>(&mut self, op_fn: F, op_label: &str) -> Result<(), CalculationError>
+    where F: FnOnce(f64) -> f64, {
+        let last_operation = self.last_operation.take();
+        let result = self.apply_op(op_fn, op_label, None);
+        self.last_operation = last_operation;
+        result
+    }
+
+    // Run a caller-supplied operation on the current value. The result goes through the same
+    // checked_value validation as the built-in operations before a node is inserted.
+    pub fn apply_custom<F: FnOnce(f64) -> f64>(&mut self, f: F, label: &str) -> Result<(), CalculationError> {
+        self.apply_unrepeatable(f, label)
+    }
+
+    // Round the current value to a whole number using the selected rounding mode
+    pub fn round(&mut self) -> Result<(), CalculationError> {
+        let mode = self.rounding_mode;
+        self.apply_op(|prev| mode.round(prev), "round", None)
+    }
+
+    // Start a pending binary operation (+ - * / ^). A complete pending operation is committed
+    // first, so entering "+ 2" then "*" applies the addition.
+    pub fn set_pending(&mut self, op: &str) -> Result<(), CalculationError> {
+        if !matches!(op, "+" | "-" | "*" | "/" | "^") {
+            return Err(CalculationError::ParseError(format!("'{}' is not a binary operator", op)));
+        }
+        if let Some((_, Some(_))) = self.pending {
+            self.commit_pending()?;
+        }
+        self.pending = Some((op.to_string(), None));
+        Ok(())
+    }
+
+    // Supply the operand for the pending operator
+    pub fn enter_operand(&mut self, value: f64) -> Result<(), CalculationError> {
+        match &mut self.pending {
+            Some((_, operand)) => {
+                *operand = Some(value);
+                Ok(())
+            }
+            None => Err(CalculationError::ParseError("No pending operator".to_string())),
+        }
+    }
+
+    pub fn pending(&self) -> Option<(&str, Option<f64>)> {
+        self.pending.as_ref().map(|(op, operand)| (op.as_str(), *operand))
+    }
+
+    // Apply the most recent operation again, with the same operand, to the current value
+    pub fn repeat_last(&mut self) -> Result<(), CalculationError> {
+        let (label, operand, extra) = self.last_operation.clone()
+            .ok_or_else(|| CalculationError::ParseError("No operation to repeat".to_string()))?;
+        self.apply_labelled(&label, operand, extra)
+    }
+
+    // The "=" key: apply the pending operation and insert its result. Ok(()) with nothing pending;
+    // an operator still waiting for its operand is an error.
+    pub fn commit(&mut self) -> Result<(), CalculationError> {
+        if let Some((op, None)) = &self.pending {
+            return Err(CalculationError::ParseError(format!("Pending '{}' needs an operand", op)));
+        }
+        self.commit_pending()
+    }
+
+    // Apply the pending operation if its operand has been entered; it stays pending on failure
+    fn commit_pending(&mut self) -> Result<(), CalculationError> {
+        if let Some((op, Some(operand))) = self.pending.clone() {
+            self.apply_labelled(&op, Some(operand), None)?;
+            self.pending = None;
+        }
+        Ok(())
+    }
+
+    // Dry run of an operation on the current value: the validated result, with no node inserted.
+    // The closure carries no operation label, so in Decimal mode it runs in f64 rather than through
+    // decimal_step and may differ from the real step in the last digits (0.1 + 0.2 previews as
+    // 0.30000000000000004 but stores 0.3).
+    pub fn preview(&self, op: impl Fn(f64) -> f64) -> Result<f64, CalculationError> {
+        let prev = self.nodes[self.current].value;
+        let candidate = match self.calc_mode {
+            CalcMode::Real => op(prev),
+            CalcMode::Integer => op(prev).trunc(),
+            #[cfg(feature = "decimal")]
+            CalcMode::Decimal => op(prev),
+        };
+        self.checked_value(prev, candidate)
+    }
+
+    // Basic arithmetic in Decimal, starting from each value's shortest decimal form (the value the
+    // tree stores and displays). None for other operations, or values outside Decimal's range,
+    // which fall back to f64.
+    #[cfg(feature = "decimal")]
+    fn decimal_step(op_label: &str, prev: f64, operand: Option<f64>) -> Option<f64> {
+        let lhs: Decimal = prev.to_string().parse().ok()?;
+        let rhs: Decimal = operand?.to_string().parse().ok()?;
+        let result = match op_label {
+            "+" => lhs.checked_add(rhs),
+            "-" => lhs.checked_sub(rhs),
+            "*" => lhs.checked_mul(rhs),
+            "/" => lhs.checked_div(rhs),
+            _ => None,
+        }?;
+        result.to_f64()
+    }
+
+    // Record a failed operation in the transcript and hand back its error
+    fn reject(&mut self, op_label: &str, operand: Option<f64>, error: CalculationError) -> Result<(), CalculationError> {
+        self.log_operation(op_label, operand, &format!("failed: {}", error));
+        Err(error)
+    }
+
+    fn log_operation(&mut self, op_label: &str, operand: Option<f64>, outcome: &str) {
+        let name = match op_label {
+            "+" => "add",
+            "-" => "subtract",
+            "*" => "multiply",
+            "/" => "divide",
+            "^" => "exp",
+            "√" => "sqrt",
+            "sqr" => "square",
+            other => other,
+        };
+        let entry = match operand {
+            Some(x) => format!("{} {} -> {}", name, x, outcome),
+            None => format!("{} -> {}", name, outcome),
+        };
+        self.transcript.push(entry);
+    }
+
+    // Transcript entry for a step that inserts its node itself rather than through apply_op
+    fn log_insert(&mut self, op_label: &str, operand: Option<f64>, result: Result<f64, CalculationError>) -> Result<f64, CalculationError> {
+        match &result {
+            Ok(value) => self.log_operation(op_label, operand, &value.to_string()),
+            Err(e) => self.log_operation(op_label, operand, &format!("failed: {}", e)),
+        }
+        result
+    }
+
+    // Every operation attempted so far, including ones that failed or were later undone
+    pub fn transcript(&self) -> &[String] {
+        &self.transcript
+    }
+
+    // Re-run a recorded operation from its label, operand and (for two-input steps) extra input
+    fn apply_labelled(&mut self, label: &str, operand: Option<f64>, extra: Option<f64>) -> Result<(), CalculationError> {
+        let needs_operand = || CalculationError::ParseError(format!("Operation '{}' needs an operand", label));
+        match label {
+            "+" => self.add(operand.ok_or_else(needs_operand)?),
+            "-" => self.subtract(operand.ok_or_else(needs_operand)?),
+            "*" => self.multiply(operand.ok_or_else(needs_operand)?),
+            "/" => self.divide(operand.ok_or_else(needs_operand)?),
+            "^" => self.exp(operand.ok_or_else(needs_operand)?),
+            "sqr" => self.square(),
+            "√" => self.square_root(),
+            "ln" => self.natural_log(),
+            "≈2ⁿ" => self.nearest_power_of_two(),
+            "atan2" => self.atan2(operand.ok_or_else(needs_operand)?),
+            "hypot" => self.hypot(operand.ok_or_else(needs_operand)?),
+            "np2" => self.next_power_of_two(),
+            "round" => self.round(),
+            "==" => self.equals(operand.ok_or_else(needs_operand)?),
+            "<" => self.less_than(operand.ok_or_else(needs_operand)?),
+            ">" => self.greater_than(operand.ok_or_else(needs_operand)?),
+            "Q" => self.to_fixed_point(operand.ok_or_else(needs_operand)? as u32),
+            "EAR" => self.effective_rate(operand.ok_or_else(needs_operand)?),
+            "PV" => self.present_value(operand.ok_or_else(needs_operand)?, extra.ok_or_else(needs_operand)?),
+            "compound" => self.compound_interest(operand.ok_or_else(needs_operand)?, extra.ok_or_else(needs_operand)?),
+            "tetr" => self.tetrate(operand.ok_or_else(needs_operand)? as u32),
+            "&" => self.bit_and(operand.ok_or_else(needs_operand)?),
+            "|" => self.bit_or(operand.ok_or_else(needs_operand)?),
+            "^^" => self.bit_xor(operand.ok_or_else(needs_operand)?),
+            "<<" => self.shift_left(operand.ok_or_else(needs_operand)?),
+            ">>" => self.shift_right(operand.ok_or_else(needs_operand)?),
+            _ if self.custom_ops.contains_key(label) => self.apply_registered(label),
+            _ if label.contains('→') => {
+                let (from, to) = label.split_once('→').unwrap_or_default();
+                match conversions::unit_category(from) {
+                    Some(category) => self.convert_unit(category, from, to),
+                    None => self.convert_temperature(from, to),
+                }
+            }
+            _ => match label.split_once('∘') {
+                Some((second, first)) => self.compose_ops(first, second),
+                None => Err(CalculationError::ParseError(format!("Cannot replay operation '{}'", label))),
+            },
+        }
+    }
+
+    // Parse a textual operation such as "+ 5", "*2" or "sqrt" into its node label and operand
+    fn parse_op(text: &str) -> Result<(String, Option<f64>), CalculationError> {
+        let text = text.trim();
+        match text {
+            "sqrt" | "√" => return Ok(("√".to_string(), None)),
+            "sqr" | "ln" => return Ok((text.to_string(), None)),
+            _ => {}
+        }
+        let mut chars = text.chars();
+        match chars.next() {
+            Some(symbol @ ('+' | '-' | '*' | '/' | '^')) => {
+                Ok((symbol.to_string(), Some(parse_number(chars.as_str())?)))
+            }
+            _ => Err(CalculationError::ParseError(format!("Unknown operation '{}'", text))),
+        }
+    }
+
+    // Result of one basic operation on prev without touching the tree, or None if it would fail
+    fn eval_step(&self, label: &str, operand: Option<f64>, prev: f64) -> Option<f64> {
+        let candidate = match (label, operand) {
+            ("+", Some(x)) => prev + x,
+            ("-", Some(x)) => prev - x,
+            ("*", Some(x)) => prev * x,
+            ("/", Some(x)) if x != 0.0 => prev / x,
+            ("^", Some(x)) => prev.powf(x),
+            ("sqr", _) => prev * prev,
+            ("√", _) if prev >= 0.0 => prev.sqrt(),
+            ("ln", _) if prev > 0.0 => prev.ln(),
+            _ => return None,
+        };
+        self.checked_value(prev, candidate).ok()
+    }
+
+    // Breadth-first search for the shortest sequence of ops (e.g. "+1", "*2", "sqrt") taking the
+    // current value to within epsilon of target. Op strings that cannot be parsed are ignored.
+    pub fn path_to_target(&self, target: f64, ops: &[&str], max_depth: usize, epsilon: f64) -> Option<Vec<String>> {
+        let steps: Vec<(usize, String, Option<f64>)> = ops.iter().enumerate()
+            .filter_map(|(i, op)| Self::parse_op(op).ok().map(|(label, operand)| (i, label, operand)))
+            .collect();
+        let start = self.nodes[self.current].value;
+        let mut queue = VecDeque::from([(start, Vec::new())]);
+        let mut seen = HashSet::from([start.to_bits()]);
+
+        while let Some((value, path)) = queue.pop_front() {
+            if (value - target).abs() <= epsilon {
+                return Some(path.iter().map(|&i: &usize| ops[i].to_string()).collect());
+            }
+            if path.len() >= max_depth { continue; }
+            for (i, label, operand) in &steps {
+                if let Some(next) = self.eval_step(label, *operand, value) && seen.insert(next.to_bits()) {
+                    let mut next_path = path.clone();
+                    next_path.push(*i);
+                    queue.push_back((next, next_path));
+                }
+            }
+        }
+        None
+    }
+
+    // Re-apply every operation on the history path up to current, starting from the current value.
+    // Stops with a ParseError at steps whose inputs are not stored (sum, mean, stddev, lerp, apply_custom).
+    pub fn replay_as_branch(&mut self) -> Result<(), CalculationError> {
+        let ops: Vec<(String, Option<f64>, Option<f64>)> = self.history[1..=self.history_index].iter()
+            .filter_map(|&node| {
+                let n = &self.nodes[node];
+                n.last_op.clone().map(|op| (op, n.operand, n.extra))
+            })
+            .collect();
+        for (label, operand, extra) in ops {
+            self.apply_labelled(&label, operand, extra)?;
+        }
+        Ok(())
+    }
+
+    // Apply recorded operations in order (e.g. "+ 5", "sqrt"), stopping at the first failure
+    pub fn replay(&mut self, ops: &[String]) -> Result<(), CalculationError> {
+        for op in ops {
+            let (label, operand) = Self::parse_op(op)?;
+            self.apply_labelled(&label, operand, None)?;
+        }
+        Ok(())
+    }
+
+    // Apply (operator, operand) pairs in order: + - * / ^ take an operand, while 's' (sqrt),
+    // 'q' (square) and 'l' (ln) take None. Nodes from steps before a failure are kept.
+    pub fn apply_batch(&mut self, ops: &[(char, Option<f64>)]) -> Result<(), CalculationError> {
+        for &(op, operand) in ops {
+            let label = match op {
+                '+' | '-' | '*' | '/' | '^' => op.to_string(),
+                's' => "√".to_string(),
+                'q' => "sqr".to_string(),
+                'l' => "ln".to_string(),
+                _ => return Err(CalculationError::ParseError(format!("Unknown batch operator '{}'", op))),
+            };
+            self.apply_labelled(&label, operand, None)?;
+        }
+        Ok(())
+    }
+
+    // Replay a file holding one recorded operation per line; blank lines are skipped
+    pub fn replay_file(&mut self, path: &Path) -> Result<(), CalculationError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CalculationError::ParseError(format!("IO error reading {}: {}", path.display(), e)))?;
+        let ops: Vec<String> = text.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect();
+        self.replay(&ops)
+    }
+
+    // Execute one REPL command per line, the same commands the interactive session accepts. Values
+    // the command would prompt for come from the rest of its line or the lines after it. Failing
+    // lines are reported on stderr and the script carries on.
+    pub fn run_script<R: std::io::BufRead>(&mut self, reader: R) {
+        let lines = reader.lines().map_while(Result::ok).enumerate()
+            .map(|(i, line)| (i + 1, line.trim().to_string()))
+            .filter(|(_, line)| !line.is_empty());
+        let mut input = CommandInput { lines, script: true, line_number: 0, inline: None, ended: false };
+        while let Ok(line) = input.next_line() {
+            if Self::run_command(self, &line, &mut input).is_break() {
+                break;
+            }
+        }
+    }
+
+    // Evaluate space-separated reverse Polish notation (e.g. "3 4 + 5 *") and insert the result
+    pub fn eval_rpn(&mut self, tokens: &str) -> Result<f64, CalculationError> {
+        let result = self.insert_rpn(tokens);
+        self.log_insert(&format!("rpn '{}'", tokens.trim()), None, result)
+    }
+
+    fn insert_rpn(&mut self, tokens: &str) -> Result<f64, CalculationError> {
+        let mut stack: Vec<f64> = Vec::new();
+        let mut last_op: Option<String> = None;
+
+        for token in tokens.split_whitespace() {
+            match token {
+                "+" | "-" | "*" | "/" | "^" => {
+                    let (rhs, lhs) = match (stack.pop(), stack.pop()) {
+                        (Some(rhs), Some(lhs)) => (rhs, lhs),
+                        _ => return Err(CalculationError::ParseError(format!("Too few operands for '{}'", token))),
+                    };
+                    let value = match token {
+                        "+" => lhs + rhs,
+                        "-" => lhs - rhs,
+                        "*" => lhs * rhs,
+                        "/" => {
+                            if rhs == 0.0 { return Err(CalculationError::DivisionByZero); }
+                            lhs / rhs
+                        }
+                        _ => lhs.powf(rhs),
+                    };
+                    stack.push(value);
+                    last_op = Some(token.to_string());
+                }
+                _ => stack.push(token.parse::<f64>()?),
+            }
+        }
+
+        let result = match stack.as_slice() {
+            [value] => *value,
+            [] => return Err(CalculationError::ParseError("Empty RPN expression".to_string())),
+            rest => return Err(CalculationError::ParseError(format!("{} values left on the stack", rest.len()))),
+        };
+        let valid = self.checked_value(result, result)?;
+        self.insert_node(valid, last_op, None);
+        Ok(valid)
+    }
+
+    // Evaluate an infix expression such as "(x + 2) * 3", resolving names against stored
+    // variables, and insert the result
+    pub fn eval_expression(&mut self, expr: &str) -> Result<f64, CalculationError> {
+        let result = self.insert_expression(expr);
+        self.log_insert(&format!("expr '{}'", expr.trim()), None, result)
+    }
+
+    fn insert_expression(&mut self, expr: &str) -> Result<f64, CalculationError> {
+        let mut parser = ExpressionParser { tokens: tokenize_expression(expr)?, pos: 0, variables: &self.variables };
+        let result = parser.expression()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(CalculationError::ParseError(format!("Unexpected '{}' in expression", token)));
+        }
+        let valid = self.checked_value(result, result)?;
+        self.insert_node(valid, None, None);
+        Ok(valid)
+    }
+
+    // Add each value in turn, leaving a chain of nodes holding the running totals
+    pub fn cumulative_sum_branches(&mut self, values: &[f64]) -> Result<(), CalculationError> {
+        for &value in values {
+            self.add(value)?;
+        }
+        Ok(())
+    }
+
+    // Values along the linear history path, oldest first
+    fn history_values(&self) -> Vec<f64> {
+        self.history.iter().map(|&node| self.nodes[node].value).collect()
+    }
+
+    // Count, sum, mean, min and max over the linear history path (root included); branches
+    // that are no longer on the path are ignored, unlike tree_max/tree_min
+    pub fn history_stats(&self) -> HistoryStats {
+        let values = self.history_values();
+        let sum: f64 = values.iter().sum();
+        HistoryStats {
+            count: values.len(),
+            sum,
+            mean: sum / values.len() as f64,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+
+    // Each history entry's value and operation label, oldest first
+    pub fn history_iter(&self) -> impl Iterator<Item = (f64, Option<String>)> + '_ {
+        self.history.iter().map(move |&node| (self.nodes[node].value, self.nodes[node].last_op.clone()))
+    }
+
+    // Every node reachable from root, in pre-order (includes branches off the history path)
+    fn tree_nodes(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(self.nodes[node].child_item.iter().rev());
+        }
+        order
+    }
+
+    // Values of current's direct children, in insertion order
+    fn child_values(&self) -> Vec<f64> {
+        self.nodes[self.current].child_item.iter().map(|&child| self.nodes[child].value).collect()
+    }
+
+    // Real roots (ascending) of a·x² + b·x + c, reading a, b, c from the first three children of current
+    pub fn solve_quadratic(&self) -> Result<(f64, f64), CalculationError> {
+        let &[a, b, c, ..] = self.child_values().as_slice() else { return Err(CalculationError::InvalidChildIndex) };
+        if a == 0.0 { return Err(CalculationError::DivisionByZero); }
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 { return Err(CalculationError::OutOfBounds); }
+        let (r1, r2) = ((-b - discriminant.sqrt()) / (2.0 * a), (-b + discriminant.sqrt()) / (2.0 * a));
+        Ok((r1.min(r2), r1.max(r2)))
+    }
+
+    // Weighted average of current's children values, one weight per child
+    pub fn centroid_children(&self, weights: &[f64]) -> Result<f64, CalculationError> {
+        let values = self.child_values();
+        if values.len() != weights.len() { return Err(CalculationError::InvalidChildIndex); }
+        let total: f64 = weights.iter().sum();
+        if total == 0.0 { return Err(CalculationError::DivisionByZero); }
+        Ok(values.iter().zip(weights).map(|(v, w)| v * w).sum::<f64>() / total)
+    }
+
+    // Total nodes in the tree, unlike history.len() which only covers the linear path
+    pub fn node_count(&self) -> usize {
+        self.tree_nodes().len()
+    }
+
+    // Largest and smallest values recorded anywhere in the tree
+    pub fn tree_max(&self) -> f64 {
+        self.tree_nodes().iter().map(|&node| self.nodes[node].value).fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    pub fn tree_min(&self) -> f64 {
+        self.tree_nodes().iter().map(|&node| self.nodes[node].value).fold(f64::INFINITY, f64::min)
+    }
+
+    // Diagnostic check for NaN anywhere in the tree
+    pub fn has_nan(&self) -> bool {
+        self.tree_nodes().iter().any(|&node| self.nodes[node].value.is_nan())
+    }
+
+    // History index and size of the largest absolute change between consecutive values
+    pub fn largest_jump(&self) -> Option<(usize, f64)> {
+        self.history_values()
+            .windows(2)
+            .enumerate()
+            .map(|(i, pair)| (i + 1, (pair[1] - pair[0]).abs()))
+            .fold(None, |best: Option<(usize, f64)>, (index, jump)| match best {
+                Some((_, largest)) if largest >= jump => best,
+                _ => Some((index, jump)),
+            })
+    }
+
+    // Jump straight to a position in the linear history
+    pub fn goto_history(&mut self, index: usize) -> Result<(), CalculationError> {
+        let node = *self.history.get(index).ok_or(CalculationError::InvalidChildIndex)?;
+        self.history_index = index;
+        self.current = node;
+        Ok(())
+    }
+
+    // Move into one of current's children, making that branch the history path from here on
+    pub fn go_to_child(&mut self, index: usize) -> Result<(), CalculationError> {
+        let child = *self.nodes[self.current].child_item.get(index).ok_or(CalculationError::InvalidChildIndex)?;
+        let history = Rc::make_mut(&mut self.history);
+        history.truncate(self.history_index + 1);
+        history.push(child);
+        self.history_index += 1;
+        self.current = child;
+        self.trim_history();
+        Ok(())
+    }
+
+    // Remove one history entry from the tree, handing its children to its parent in its place.
+    // Later values are kept as they are rather than recomputed. Index 0 is the start of the history
+    // path (the root, or the oldest kept entry once history is cleared or trimmed) and is never removed.
+    pub fn delete_at(&mut self, index: usize) -> Result<(), CalculationError> {
+        let node = *self.history.get(index).ok_or(CalculationError::InvalidChildIndex)?;
+        if index == 0 { return Err(CalculationError::CannotDeleteRoot); }
+        let parent = self.nodes[node].parent.ok_or(CalculationError::CannotDeleteRoot)?;
+        let children = std::mem::take(&mut self.nodes[node].child_item);
+        for &child in &children {
+            self.nodes[child].parent = Some(parent);
+        }
+        let siblings = &mut self.nodes[parent].child_item;
+        if let Some(position) = siblings.iter().position(|&child| child == node) {
+            siblings.splice(position..=position, children);
+        }
+
+        Rc::make_mut(&mut self.history).remove(index);
+        if index <= self.history_index {
+            self.history_index -= 1;
+            if node == self.current {
+                self.current = self.history[self.history_index];
+            }
+        }
+        Ok(())
+    }
+
+    // Redo into the chosen child and restore that branch's forward path (following the most
+    // recent child at each level), so later redos continue down the same branch
+    pub fn redo_branch(&mut self, branch: usize) -> Result<(), CalculationError> {
+        self.go_to_child(branch)?;
+        let mut node = self.current;
+        let history = Rc::make_mut(&mut self.history);
+        while let Some(&next) = self.nodes[node].child_item.last() {
+            history.push(next);
+            node = next;
+        }
+        Ok(())
+    }
+
+    // Value before the current node's operation, without navigating; None at the root
+    pub fn parent_value(&self) -> Option<f64> {
+        self.nodes[self.current].parent.map(|parent| self.nodes[parent].value)
+    }
+
+    // Move to the parent node without removing anything from the tree
+    pub fn go_to_parent(&mut self) -> Result<(), CalculationError> {
+        let parent = self.nodes[self.current].parent.ok_or(CalculationError::AtRoot)?;
+        if self.history_index > 0 && self.history[self.history_index - 1] == parent {
+            self.history_index -= 1;
+        } else {
+            let history = Rc::make_mut(&mut self.history);
+            history.truncate(self.history_index + 1);
+            history.push(parent);
+            self.history_index += 1;
+        }
+        self.current = parent;
+        self.trim_history();
+        Ok(())
+    }
+
+    fn print_indexed_history(&self) {
+        println!("--- Linear History ---");
+        for (index, &node) in self.history.iter().enumerate() {
+            let marker = if index == self.history_index { "  ← current" } else { "" };
+            println!("  [{}] {}{}", index, self.format_value(self.nodes[node].value), marker);
+        }
+    }
+
+    // Undo until the current value is within epsilon of target; history is left untouched if it never is
+    pub fn undo_until(&mut self, target: f64, epsilon: f64) -> Result<(), CalculationError> {
+        let found = (0..=self.history_index).rev()
+            .find(|&i| (self.nodes[self.history[i]].value - target).abs() <= epsilon)
+            .ok_or(CalculationError::CannotGoBackwards)?;
+        while self.history_index > found {
+            self.go_backwards()?;
+        }
+        Ok(())
+    }
+
+    // Range (max minus min) of the values on the history path
+    pub fn history_span(&self) -> f64 {
+        let values = self.history_values();
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        max - min
+    }
+
+    // Percentage change from one history entry's value to another's
+    pub fn percent_change(&self, from_index: usize, to_index: usize) -> Result<f64, CalculationError> {
+        let values = self.history_values();
+        let (&from, &to) = values.get(from_index).zip(values.get(to_index)).ok_or(CalculationError::InvalidChildIndex)?;
+        if from == 0.0 { return Err(CalculationError::DivisionByZero); }
+        Ok((to - from) / from * 100.0)
+    }
+
+    // Minimum of each sliding window of the history series
+    pub fn rolling_min(&self, window: usize) -> Result<Vec<f64>, CalculationError> {
+        let values = self.history_values();
+        if window == 0 { return Err(CalculationError::OutOfBounds); }
+        if window > values.len() { return Err(CalculationError::InsufficientData); }
+        Ok(values.windows(window).map(|w| w.iter().copied().fold(f64::INFINITY, f64::min)).collect())
+    }
+
+    // Population variance of every value inserted this session, maintained in O(1) per insert.
+    // Roots count as inserted: new, reset and hard_reset each add their starting value.
+    pub fn running_variance(&self) -> f64 {
+        self.running_stats.variance()
+    }
+
+    // Lag-1 autocorrelation coefficient of the history series
+    pub fn autocorrelation_lag1(&self) -> Result<f64, CalculationError> {
+        let values = self.history_values();
+        if values.len() < 3 { return Err(CalculationError::InsufficientData); }
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+        if variance == 0.0 { return Err(CalculationError::DivisionByZero); }
+        let covariance: f64 = values.windows(2).map(|pair| (pair[0] - mean) * (pair[1] - mean)).sum();
+        Ok(covariance / variance)
+    }
+
+    // Level-by-level view of the tree, one line per node with its depth
+    fn history_bfs_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut queue = VecDeque::from([(self.root, 0)]);
+        while let Some((node, depth)) = queue.pop_front() {
+            let n = &self.nodes[node];
+            let op = n.last_op.as_ref().map(|op| format!(" | {}", op)).unwrap_or_default();
+            let marker = if node == self.current { "  ← current" } else { "" };
+            lines.push(format!("[depth {}] {}{}{}", depth, self.format_value(n.value), op, marker));
+            queue.extend(n.child_item.iter().map(|&child| (child, depth + 1)));
+        }
+        lines
+    }
+
+    pub fn show_history_bfs(&self) {
+        println!("--- Calculator History (breadth-first) ---");
+        for line in self.history_bfs_lines() {
+            println!("{}", line);
+        }
+    }
+
+    // Render the history path up to current as a LaTeX expression, e.g. \sqrt{(0 + 5) \cdot 2}.
+    // Steps without a recorded operand (direct input, RPN results) restart the expression at their value.
+    pub fn to_latex(&self) -> String {
+        // Binding strength of the expression built so far: 1 = sum, 2 = product or power, 3 = atomic
+        fn literal(value: f64) -> (String, u8) {
+            (value.to_string(), if value < 0.0 { 1 } else { 3 })
+        }
+        fn wrap((expr, strength): &(String, u8), min: u8) -> String {
+            if *strength < min { format!("({})", expr) } else { expr.clone() }
+        }
+
+        let mut expr = literal(self.nodes[self.history[0]].value);
+        for &node in &self.history[1..=self.history_index] {
+            let n = &self.nodes[node];
+            let operand = n.operand.map(|x| wrap(&literal(x), 3));
+            expr = match (n.last_op.as_deref(), operand) {
+                (Some("+"), Some(x)) => (format!("{} + {}", expr.0, x), 1),
+                (Some("-"), Some(x)) => (format!("{} - {}", expr.0, x), 1),
+                (Some("*"), Some(x)) => (format!("{} \\cdot {}", wrap(&expr, 2), x), 2),
+                (Some("/"), Some(x)) => (format!("\\frac{{{}}}{{{}}}", expr.0, x), 3),
+                (Some("^"), Some(x)) => (format!("{}^{{{}}}", wrap(&expr, 3), x), 2),
+                (Some("+" | "-" | "*" | "/" | "^") | None, _) => literal(n.value),
+                (Some("sqr"), _) => (format!("{}^{{2}}", wrap(&expr, 3)), 2),
+                (Some("√"), _) => (format!("\\sqrt{{{}}}", expr.0), 3),
+                (Some("ln"), _) => (format!("\\ln({})", expr.0), 3),
+                (Some(label), _) => (format!("\\operatorname{{{}}}({})", label, expr.0), 3),
+            };
+        }
+        expr.0
+    }
+
+    // Render the history tree as Graphviz DOT, highlighting the current node
+    pub fn to_dot(&self) -> String {
+        fn emit(nodes: &[Node], node: usize, current: usize, out: &mut String) {
+            let n = &nodes[node];
+            let style = if node == current { ", style=filled, fillcolor=lightblue" } else { "" };
+            out.push_str(&format!("    n{} [label={:?}{}];\n", node, n.value.to_string(), style));
+            for &child in &n.child_item {
+                let label = nodes[child].last_op.as_deref().unwrap_or("");
+                out.push_str(&format!("    n{} -> n{} [label={:?}];\n", node, child, label));
+                emit(nodes, child, current, out);
+            }
+        }
+
+        let mut out = String::from("digraph history {\n");
+        emit(&self.nodes, self.root, self.current, &mut out);
+        out.push_str("}\n");
+        out
+    }
+
+    // Round to the nearest multiple of 2^-fractional_bits, as a Qm.n fixed-point value would store it
+    pub fn to_fixed_point(&mut self, fractional_bits: u32) -> Result<(), CalculationError> {
+        if fractional_bits > 63 { return self.reject("Q", Some(fractional_bits as f64), CalculationError::OutOfBounds); }
+        let scale = 2f64.powi(fractional_bits as i32);
+        self.apply_op(|prev| (prev * scale).round() / scale, "Q", Some(fractional_bits as f64))
+    }
+
+    // Power tower x^(x^(...x)) of the given height; by convention height 0 gives 1 and height 1 gives x.
+    // The tower stops growing once it overflows or settles, leaving checked_value to reject runaway results.
+    // Bases below e^-e oscillate instead of settling, so heights above TETRATION_MAX_HEIGHT are OutOfBounds.
+    pub fn tetrate(&mut self, height: u32) -> Result<(), CalculationError> {
+        if height > TETRATION_MAX_HEIGHT { return self.reject("tetr", Some(height as f64), CalculationError::OutOfBounds); }
+        self.apply_op(|x| {
+            let mut tower = 1.0_f64;
+            for _ in 0..height {
+                let next = x.powf(tower);
+                if next == tower || !next.is_finite() { return next; }
+                tower = next;
+            }
+            tower
+        }, "tetr", Some(height as f64))
+    }
+
+    // Effective annual rate (percent) from the current nominal annual rate (percent)
+    pub fn effective_rate(&mut self, compounding_periods: f64) -> Result<(), CalculationError> {
+        if !(compounding_periods >= 1.0 && compounding_periods.is_finite()) {
+            return self.reject("EAR", Some(compounding_periods), CalculationError::OutOfBounds);
+        }
+        self.apply_op(|nominal| ((1.0 + nominal / 100.0 / compounding_periods).powf(compounding_periods) - 1.0) * 100.0,
+                      "EAR", Some(compounding_periods))
+    }
+
+    // Discount the current (future) value back over periods at rate_percent per period
+    pub fn present_value(&mut self, rate_percent: f64, periods: f64) -> Result<(), CalculationError> {
+        if !rate_percent.is_finite() || !periods.is_finite() { return self.reject("PV", Some(rate_percent), CalculationError::OutOfBounds); }
+        self.apply_op_with_extra(|future| future / (1.0 + rate_percent / 100.0).powf(periods), "PV", Some(rate_percent), Some(periods))
+    }
+
+    // Grow the current principal at rate (a fraction, 0.05 = 5%) per period, compounded over periods
+    pub fn compound_interest(&mut self, rate: f64, periods: f64) -> Result<(), CalculationError> {
+        if rate <= -1.0 { return self.reject("compound", Some(rate), CalculationError::OutOfBounds); }
+        self.apply_op_with_extra(|principal| principal * (1.0 + rate).powf(periods), "compound", Some(rate), Some(periods))
+    }
+
+    // Sum of values, inserted as a new node (an empty slice sums to 0)
+    pub fn sum_of(&mut self, values: &[f64]) -> Result<(), CalculationError> {
+        let sum: f64 = values.iter().sum();
+        self.apply_unrepeatable(|_| sum, "sum")
+    }
+
+    // Arithmetic mean of values, inserted as a new node
+    pub fn mean_of(&mut self, values: &[f64]) -> Result<(), CalculationError> {
+        if values.is_empty() { return self.reject("mean", None, CalculationError::ParseError("No values given".to_string())); }
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        self.apply_unrepeatable(|_| mean, "mean")
+    }
+
+    // Population standard deviation of values, inserted as a new node
+    pub fn stddev_of(&mut self, values: &[f64]) -> Result<(), CalculationError> {
+        if values.is_empty() { return self.reject("stddev", None, CalculationError::ParseError("No values given".to_string())); }
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        self.apply_unrepeatable(|_| variance.sqrt(), "stddev")
+    }
+
+    // Spell out the integer part of the current value in English (magnitudes below one quadrillion)
+    pub fn to_words(&self) -> Result<String, CalculationError> {
+        const ONES: [&str; 20] = ["zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+            "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen"];
+        const TENS: [&str; 10] = ["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+        const SCALES: [(u64, &str); 4] = [(1_000_000_000_000, "trillion"), (1_000_000_000, "billion"), (1_000_000, "million"), (1_000, "thousand")];
+
+        fn below_thousand(n: u64) -> String {
+            let mut parts = Vec::new();
+            if n >= 100 { parts.push(format!("{} hundred", ONES[(n / 100) as usize])); }
+            match n % 100 {
+                0 => {}
+                rest @ 1..=19 => parts.push(ONES[rest as usize].to_string()),
+                rest if rest % 10 == 0 => parts.push(TENS[(rest / 10) as usize].to_string()),
+                rest => parts.push(format!("{}-{}", TENS[(rest / 10) as usize], ONES[(rest % 10) as usize])),
+            }
+            parts.join(" ")
+        }
+
+        let whole = self.nodes[self.current].value.trunc();
+        if !whole.is_finite() || whole.abs() >= 1e15 { return Err(CalculationError::OutOfBounds); }
+        let mut n = whole.abs() as u64;
+        if n == 0 { return Ok(ONES[0].to_string()); }
+
+        let mut words = Vec::new();
+        if whole < 0.0 { words.push("negative".to_string()); }
+        for (scale, name) in SCALES {
+            if n >= scale {
+                words.push(format!("{} {}", below_thousand(n / scale), name));
+                n %= scale;
+            }
+        }
+        if n > 0 { words.push(below_thousand(n)); }
+        Ok(words.join(" "))
+    }
+
+    // Significant figures of the current value in its shortest decimal form. Leading zeros never count;
+    // trailing zeros of a whole number are ambiguous and not counted (120300 -> 4). An f64 cannot keep
+    // trailing zeros after the decimal point, so 0.00340 is indistinguishable from 0.0034 (-> 2).
+    pub fn significant_figures(&self) -> u32 {
+        let value = self.nodes[self.current].value;
+        if value == 0.0 || !value.is_finite() { return 0; }
+        let plain = format!("{}", value.abs());
+        let digits = plain.replace('.', "");
+        let digits = digits.trim_start_matches('0');
+        let digits = if plain.contains('.') { digits } else { digits.trim_end_matches('0') };
+        digits.len() as u32
+    }
+
+    // Render a whole current value in base 2, 8 or 16, e.g. 255 -> "ff"
+    pub fn show_radix(&self, radix: u32) -> Result<String, CalculationError> {
+        let value = RustyCalculator::whole_i64(self.nodes[self.current].value)?;
+        let sign = if value < 0 { "-" } else { "" };
+        let magnitude = value.unsigned_abs();
+        match radix {
+            2 => Ok(format!("{}{:b}", sign, magnitude)),
+            8 => Ok(format!("{}{:o}", sign, magnitude)),
+            16 => Ok(format!("{}{:x}", sign, magnitude)),
+            _ => Err(CalculationError::OutOfBounds),
+        }
+    }
+
+    // Individual decimal digits of the integer part, for reading a value back one digit at a time
+    pub fn readback_digits(&self) -> Vec<u8> {
+        let whole = self.nodes[self.current].value.trunc().abs();
+        if !whole.is_finite() { return Vec::new(); }
+        format!("{:.0}", whole).bytes().map(|b| b - b'0').collect()
+    }
+
+    // Register a named operation; re-registering a name replaces it
+    pub fn register_op<F>(&mut self, name: &str, op: F) where F: Fn(f64) -> f64 + 'static, {
+        self.custom_ops.insert(name.to_string(), Rc::new(op));
+    }
+
+    fn registered_op(&self, name: &str) -> Result<Rc<dyn Fn(f64) -> f64>, CalculationError> {
+        self.custom_ops.get(name).cloned()
+            .ok_or_else(|| CalculationError::ParseError(format!("Unknown operation '{}'", name)))
+    }
+
+    pub fn apply_registered(&mut self, name: &str) -> Result<(), CalculationError> {
+        match self.registered_op(name) {
+            Ok(op) => self.apply_op(|prev| op(prev), name, None),
+            Err(e) => self.reject(name, None, e),
+        }
+    }
+
+    // Apply first then second as a single history step labelled "second∘first"
+    pub fn compose_ops(&mut self, first: &str, second: &str) -> Result<(), CalculationError> {
+        let label = format!("{}∘{}", second, first);
+        match self.registered_op(first).and_then(|f| Ok((f, self.registered_op(second)?))) {
+            Ok((f, g)) => self.apply_op(|prev| g(f(prev)), &label, None),
+            Err(e) => self.reject(&label, None, e),
+        }
+    }
+
+    // Linear history as CSV rows of index,value,operation
+    pub fn history_to_csv(&self) -> String {
+        let mut csv = String::from("index,value,operation\n");
+        for (index, &node) in self.history.iter().enumerate() {
+            let n = &self.nodes[node];
+            let op = match n.last_op.as_deref() {
+                Some(op) if op.contains([',', '"', '\n']) => format!("\"{}\"", op.replace('"', "\"\"")),
+                Some(op) => op.to_string(),
+                None => String::new(),
+            };
+            csv.push_str(&format!("{},{},{}\n", index, n.value, op));
+        }
+        csv
+    }
+
+    // First count terms of the continued fraction expansion of the current value.
+    // Stops early once the expansion terminates (the remainder is effectively zero). At most
+    // CONTINUED_FRACTION_MAX_TERMS are returned: later terms only expand f64 rounding noise.
+    pub fn continued_fraction_terms(&self, count: usize) -> Vec<i64> {
+        Self::continued_fraction_of(self.nodes[self.current].value, count)
+    }
+
+    fn continued_fraction_of(mut x: f64, count: usize) -> Vec<i64> {
+        let count = count.min(CONTINUED_FRACTION_MAX_TERMS);
+        let mut terms = Vec::with_capacity(count);
+        while terms.len() < count && x.is_finite() && x.abs() < i64::MAX as f64 {
+            let term = x.floor();
+            terms.push(term as i64);
+            let fraction = x - term;
+            if fraction < 1e-12 { break; }
+            x = 1.0 / fraction;
+        }
+        terms
+    }
+
+    // Best continued-fraction convergent p/q of the current value with q <= max_denominator,
+    // e.g. 0.75 -> (3, 4); the sign is carried by the numerator
+    pub fn as_fraction(&self, max_denominator: u64) -> Option<(i64, i64)> {
+        Self::fraction_of(self.nodes[self.current].value, max_denominator)
+    }
+
+    fn fraction_of(value: f64, max_denominator: u64) -> Option<(i64, i64)> {
+        if max_denominator == 0 { return None; }
+        let (mut h, mut h_prev, mut k, mut k_prev) = (1i128, 0i128, 0i128, 1i128);
+        let mut best = None;
+        for term in Self::continued_fraction_of(value, CONTINUED_FRACTION_MAX_TERMS) {
+            let term = term as i128;
+            let (h_next, k_next) = (term * h + h_prev, term * k + k_prev);
+            if k_next > max_denominator as i128 { break; }
+            (h_prev, h, k_prev, k) = (h, h_next, k, k_next);
+            best = Some((i64::try_from(h).ok()?, i64::try_from(k).ok()?));
+        }
+        best
+    }
+
+    // Treat the current value as a temperature in `from` (C, F or K) and insert it in `to`
+    pub fn convert_temperature(&mut self, from: &str, to: &str) -> Result<(), CalculationError> {
+        let label = format!("{}→{}", from.to_ascii_uppercase(), to.to_ascii_uppercase());
+        match conversions::temperature(self.nodes[self.current].value, from, to) {
+            Ok(converted) => self.apply_op(|_| converted, &label, None),
+            Err(e) => self.reject(&label, None, e),
+        }
+    }
+
+    // Treat the current value as a quantity in `from` and insert it in `to`; category is
+    // "length" (m, cm, km, in, ft, mi) or "weight" (g, kg, lb, oz)
+    pub fn convert_unit(&mut self, category: &str, from: &str, to: &str) -> Result<(), CalculationError> {
+        let label = format!("{}→{}", from.to_ascii_lowercase(), to.to_ascii_lowercase());
+        match conversions::unit(self.nodes[self.current].value, category, from, to) {
+            Ok(converted) => self.apply_op(|_| converted, &label, None),
+            Err(e) => self.reject(&label, None, e),
+        }
+    }
+
+    pub fn set_calc_mode(&mut self, mode: CalcMode) {
+        self.calc_mode = mode;
+    }
+
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
+    }
+
+    // Limit how many history entries undo/redo can reach; None keeps the full history
+    pub fn set_max_history(&mut self, max: Option<usize>) {
+        self.max_history = max;
+        self.trim_history();
+    }
+
+    // Quiet (false) drops the REPL's banners and prompts, leaving bare results and errors
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    // Switching modes discards anything still pending
+    pub fn set_entry_mode(&mut self, mode: EntryMode) {
+        self.entry_mode = mode;
+        self.pending = None;
+    }
+
+    pub fn set_equals_epsilon(&mut self, epsilon: f64) {
+        self.equals_epsilon = epsilon;
+    }
+
+    pub fn set_rounding_mode(&mut self, mode: RoundingMode) {
+        self.rounding_mode = mode;
+    }
+
+    pub fn set_output_format(&mut self, format: OutputFormat) {
+        self.output_format = format;
+    }
+
+    // Raise or lower the digit count at which results are rejected with PrecisionLoss (default 15)
+    pub fn set_max_significant_digits(&mut self, digits: i32) {
+        self.max_significant_digits = digits;
     }
 
-    // Convenience method for root nodes (maintains existing API)
-    fn new_root(value: f64) -> Rc<RefCell<Self>> {
-        Self::new(value, None, None)
+    pub fn set_group_digits(&mut self, group: bool) {
+        self.group_digits = group;
     }
-}
 
-pub struct RustyCalculator {
-    root: Rc<RefCell<Node>>,
-    current: Rc<RefCell<Node>>,
-    history: Vec<Rc<RefCell<Node>>>,
-    history_index: usize,
-    snapshots: Vec<CalculatorSnapshot>,  // Store complete calculator states
-}
+    // When set, displayed values that a fraction with denominator up to 1000 reproduces are
+    // followed by it, e.g. "0.75 (3/4)"; whole numbers are shown as they are
+    pub fn set_fraction_display(&mut self, fractions: bool) {
+        self.fraction_display = fractions;
+    }
 
-impl RustyCalculator {
-    pub fn new(rest_state: f64) -> RustyCalculator {
-        let root = Node::new_root(rest_state);
-        Self {
-            root: Rc::clone(&root),
-            current: Rc::clone(&root),
-            history: vec![Rc::clone(&root)],
-            history_index: 0,
-            snapshots: Vec::new(),
+    // When set, displayed values within epsilon of a shorter decimal are shown as that decimal.
+    // Stored node values are never changed.
+    pub fn set_round_display_epsilon(&mut self, epsilon: Option<f64>) {
+        self.round_display_epsilon = epsilon;
+    }
+
+    // Nearest value with the fewest decimal places that lies within epsilon
+    fn snap_to_nice(&self, value: f64, epsilon: f64) -> f64 {
+        (0..=15).map(|places| {
+            let scale = 10f64.powi(places);
+            self.rounding_mode.nearest(value * scale) / scale
+        })
+        .find(|nice| (nice - value).abs() <= epsilon)
+        .unwrap_or(value)
+    }
+
+    // Shared formatting helper for everything that displays a value
+    pub fn format_value(&self, value: f64) -> String {
+        let value = match self.round_display_epsilon {
+            Some(epsilon) if value.is_finite() => self.snap_to_nice(value, epsilon),
+            _ => value,
+        };
+        let formatted = match self.output_format {
+            OutputFormat::Decimal if self.group_digits => Self::format_grouped(value),
+            OutputFormat::Decimal => format!("{}", value),
+            OutputFormat::Scientific => format!("{:e}", value),
+            OutputFormat::Engineering => Self::format_engineering(value),
+        };
+        match Self::fraction_of(value, FRACTION_MAX_DENOMINATOR) {
+            Some((numerator, denominator)) if self.fraction_display && denominator > 1
+                && (numerator as f64 / denominator as f64 - value).abs() <= FRACTION_DISPLAY_TOLERANCE =>
+                format!("{} ({}/{})", formatted, numerator, denominator),
+            _ => formatted,
         }
     }
 
-    fn insert_node(&mut self, value: f64, op: Option<String>) -> Rc<RefCell<Node>> {
-        let new_node = Node::new(value, Some(&self.current), op);
-        self.current.borrow_mut().child_item.push(Rc::clone(&new_node));
+    // Insert thousands separators into the integer portion only, e.g. -1234567.5 -> -1,234,567.5
+    fn format_grouped(value: f64) -> String {
+        let plain = format!("{}", value);
+        let (sign, unsigned) = match plain.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", plain.as_str()),
+        };
+        let (whole, fraction) = match unsigned.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (unsigned, None),
+        };
+        if !whole.bytes().all(|b| b.is_ascii_digit()) { return plain; }  // inf / NaN
 
-        // When creating new nodes, truncate history after current position and add new node
-        self.history.truncate(self.history_index + 1);
-        self.history.push(Rc::clone(&new_node));
-        self.history_index = self.history.len() - 1; // always point to last node
-        self.current = Rc::clone(&new_node);
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 { grouped.push(','); }
+            grouped.push(digit);
+        }
+        match fraction {
+            Some(fraction) => format!("{}{}.{}", sign, grouped, fraction),
+            None => format!("{}{}", sign, grouped),
+        }
+    }
 
-        new_node
+    // Shift the decimal point of the scientific form so the exponent is a multiple of 3
+    fn format_engineering(value: f64) -> String {
+        let scientific = format!("{:e}", value);
+        if value == 0.0 || !value.is_finite() { return scientific; }
+        let Some((mantissa, exponent)) = scientific.split_once('e') else { return scientific };
+        let Ok(exponent) = exponent.parse::<i32>() else { return scientific };
+
+        let shift = exponent.rem_euclid(3) as usize;
+        let (sign, mantissa) = match mantissa.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", mantissa),
+        };
+        let mut digits = mantissa.replace('.', "");
+        while digits.len() <= shift { digits.push('0'); }
+        let (whole, fraction) = digits.split_at(shift + 1);
+        if fraction.is_empty() {
+            format!("{}{}e{}", sign, whole, exponent - shift as i32)
+        } else {
+            format!("{}{}.{}e{}", sign, whole, fraction, exponent - shift as i32)
+        }
     }
 
-    // Apply operation with automatic last_op tracking
-    fn apply_op<F>(&mut self, op_fn: F, op_label: &str) -> Result<(), CalculationError>
-    where F: FnOnce(f64) -> f64, {
-        let prev = self.current.borrow().value;
-        let candidate = op_fn(prev);
+    fn current_display(&self) -> String {
+        self.format_value(self.nodes[self.current].value)
+    }
 
-        // Insert node with operation label
-        self.insert_node(candidate, Some(op_label.to_string()));
+    // REPL status line: value, angle mode, output format, then any arithmetic mode and pending operation,
+    // e.g. "Current value: 1.5 [RAD] [DEC]"
+    pub fn prompt_line(&self) -> String {
+        let angle = match self.angle_mode {
+            AngleMode::Radians => "[RAD]",
+            AngleMode::Degrees => "[DEG]",
+        };
+        let format = match self.output_format {
+            OutputFormat::Decimal => "[DEC]",
+            OutputFormat::Scientific => "[SCI]",
+            OutputFormat::Engineering => "[ENG]",
+        };
+        let mode = match self.calc_mode {
+            CalcMode::Real => "",
+            CalcMode::Integer => " [INT]",
+            #[cfg(feature = "decimal")]
+            CalcMode::Decimal => " [EXACT]",
+        };
+        let pending = match self.pending() {
+            Some((op, Some(operand))) => format!(" (pending {} {})", op, self.format_value(operand)),
+            Some((op, None)) => format!(" (pending {})", op),
+            None => String::new(),
+        };
+        format!("Current value: {} {} {}{}{}", highlight(&self.current_display()), angle, format, mode, pending)
+    }
 
-        match RustyCalculator::checked_value(prev, candidate) {
-            Ok(valid) => {
-                self.current.borrow_mut().value = valid;
-                Ok(())
-            }
-            Err(e) => {
-                let _ = self.delete();
-                Err(e)
-            }
-        }
+    // Multi-line overview of the calculator's state for the "info" command
+    pub fn status_string(&self) -> String {
+        [
+            format!("Current value:  {}", self.current_display()),
+            format!("History length: {}", self.history.len()),
+            format!("History index:  {}", self.history_index),
+            format!("Snapshots:      {}", self.snapshots.len()),
+            format!("Memory:         {}", self.format_value(self.memory[0])),
+            format!("Angle mode:     {:?}", self.angle_mode),
+            format!("Output format:  {:?}", self.output_format),
+        ].join("\n")
     }
 
     pub fn show(&self) {
-        println!("{}", self.current.borrow().value);
+        println!("{}", self.current_display());
     }
 
     // Store complete calculator state including root, current, and full history
     pub fn snapshot(&mut self) {
-        let snapshot = CalculatorSnapshot {
-            root: Rc::clone(&self.root),
-            current: Rc::clone(&self.current),
-            history: self.history.clone(),
+        let snapshot = self.capture();
+        self.snapshots.push(snapshot);
+    }
+
+    fn capture(&self) -> CalculatorSnapshot {
+        CalculatorSnapshot {
+            root: self.root,
+            current: self.current,
+            history: Rc::clone(&self.history),
             history_index: self.history_index,
+        }
+    }
+
+    fn restore(&mut self, snapshot: CalculatorSnapshot) {
+        self.root = snapshot.root;
+        self.current = snapshot.current;
+        self.history = snapshot.history;
+        self.history_index = snapshot.history_index;
+    }
+
+    // Index and current value of each LIFO snapshot, oldest first
+    pub fn list_snapshots(&self) -> Vec<(usize, f64)> {
+        self.snapshots.iter().enumerate().map(|(i, snapshot)| (i, self.nodes[snapshot.current].value)).collect()
+    }
+
+    // Save the current state under a name, replacing any earlier snapshot with that name
+    pub fn snapshot_named(&mut self, name: &str) {
+        let snapshot = self.capture();
+        self.named_snapshots.insert(name.to_string(), snapshot);
+    }
+
+    // Restore a named snapshot; it stays available for later restores
+    pub fn recover_named(&mut self, name: &str) -> Result<(), CalculationError> {
+        let snapshot = self.named_snapshots.get(name).cloned()
+            .ok_or_else(|| CalculationError::ParseError(format!("Unknown snapshot '{}'", name)))?;
+        self.restore(snapshot);
+        Ok(())
+    }
+
+    // Reset to 0 without saving a snapshot: the whole arena and every snapshot are dropped, so
+    // nothing is recoverable afterwards. Settings, variables and memory registers are kept.
+    pub fn hard_reset(&mut self) {
+        self.nodes = vec![Node::new_root(0.0)];
+        self.root = 0;
+        self.current = 0;
+        self.history = Rc::new(vec![0]);
+        self.history_index = 0;
+        self.snapshots.clear();
+        self.named_snapshots.clear();
+        self.running_stats = RunningStats::default();
+        self.running_stats.push(0.0);
+        self.pending = None;
+        self.last_operation = None;
+    }
+
+    // Insert the linear interpolation between two snapshots' current values
+    pub fn blend_snapshots(&mut self, a: usize, b: usize, t: f64) -> Result<(), CalculationError> {
+        if !(0.0..=1.0).contains(&t) { return self.reject("lerp", Some(t), CalculationError::OutOfBounds); }
+        let value_of = |index: usize| self.snapshots.get(index)
+            .map(|snapshot| self.nodes[snapshot.current].value)
+            .ok_or(CalculationError::InvalidChildIndex);
+        let (from, to) = match value_of(a).and_then(|from| Ok((from, value_of(b)?))) {
+            Ok(values) => values,
+            Err(e) => return self.reject("lerp", Some(t), e),
         };
-        self.snapshots.push(snapshot);
+        self.apply_unrepeatable(|_| from + (to - from) * t, "lerp")
+    }
+
+    // Keep only the path from root to current, discarding every other branch (snapshotted first)
+    pub fn prune(&mut self) {
+        self.snapshot();
+        let steps = self.path_to_current().iter()
+            .map(|&node| { let n = &self.nodes[node]; (n.value, n.last_op.clone(), n.operand, n.extra) })
+            .collect();
+        self.rebuild_as_chain(steps);
+    }
+
+    // Merge runs of the same +, -, * or / along the current path into one node with the
+    // combined operand (e.g. ten "+ 1" steps become one "+ 10"). Each merged node keeps the
+    // value of the last step it replaces, so the final value is unchanged.
+    pub fn compress_chain(&mut self) {
+        self.snapshot();
+        let mut steps: Vec<ChainStep> = Vec::new();
+        for node in self.path_to_current() {
+            let Node { value, last_op, operand, extra, .. } = &self.nodes[node];
+            if let (Some((prev_value, Some(prev_op), Some(prev_operand), _)), Some(op), Some(x)) = (steps.last_mut(), last_op, operand)
+                && prev_op == op
+            {
+                let merged = match op.as_str() {
+                    "+" | "-" => Some(*prev_operand + x),
+                    "*" | "/" => Some(*prev_operand * x),
+                    _ => None,
+                };
+                if let Some(merged) = merged {
+                    *prev_value = *value;
+                    *prev_operand = merged;
+                    continue;
+                }
+            }
+            steps.push((*value, last_op.clone(), *operand, *extra));
+        }
+        self.rebuild_as_chain(steps);
+    }
+
+    // Restart undo/redo from the current node; the value and the tree are left untouched
+    pub fn clear_history(&mut self) {
+        self.history = Rc::new(vec![self.current]);
+        self.history_index = 0;
+    }
+
+    // Accept the current value and start clean from it; unlike reset() the value is kept
+    pub fn collapse_to_value(&mut self) {
+        self.snapshot();
+        let value = self.nodes[self.current].value;
+        self.rebuild_as_chain(vec![(value, None, None, None)]);
+    }
+
+    // Remember the current value under a name
+    pub fn store_var(&mut self, name: &str) {
+        self.variables.insert(name.to_string(), self.nodes[self.current].value);
+    }
+
+    // Bring a stored value back as a new node
+    pub fn recall_var(&mut self, name: &str) -> Result<(), CalculationError> {
+        let value = self.variables.get(name).copied()
+            .ok_or_else(|| CalculationError::ParseError(format!("Unknown variable '{}'", name)));
+        let value = self.log_insert(&format!("recall {}", name), None, value)?;
+        self.insert_node(value, None, None);
+        Ok(())
+    }
+
+    // Insert a copy of the value `back` steps before the current one on the history path
+    pub fn recall_offset(&mut self, back: usize) -> Result<(), CalculationError> {
+        let value = self.history_index.checked_sub(back)
+            .map(|index| self.nodes[self.history[index]].value)
+            .ok_or(CalculationError::CannotGoBackwards);
+        let value = self.log_insert("ans", Some(back as f64), value)?;
+        self.insert_node(value, None, None);
+        Ok(())
+    }
+
+    // Copy the current value into a memory slot (0-9)
+    pub fn memory_store(&mut self, slot: usize) -> Result<(), CalculationError> {
+        *self.memory.get_mut(slot).ok_or(CalculationError::InvalidChildIndex)? = self.nodes[self.current].value;
+        Ok(())
+    }
+
+    // Insert a memory slot's value as a new node
+    pub fn memory_recall(&mut self, slot: usize) -> Result<(), CalculationError> {
+        let value = self.memory.get(slot).copied().ok_or(CalculationError::InvalidChildIndex);
+        let value = self.log_insert("mr", Some(slot as f64), value)?;
+        self.insert_node(value, None, None);
+        Ok(())
+    }
+
+    // Exchange the current value with memory slot 0 (x<>M); the old memory value becomes a new node
+    pub fn memory_swap(&mut self) {
+        let recalled = std::mem::replace(&mut self.memory[0], self.nodes[self.current].value);
+        self.insert_node(recalled, None, None);
+        self.log_operation("mx", None, &recalled.to_string());
+    }
+
+    pub fn memory_clear_all(&mut self) {
+        self.memory = [0.0; MEMORY_SLOTS];
+    }
+
+    // Add the current value to the accumulator
+    pub fn accumulate(&mut self) {
+        self.accumulator += self.nodes[self.current].value;
+    }
+
+    pub fn accumulator(&self) -> f64 {
+        self.accumulator
+    }
+
+    pub fn clear_accumulator(&mut self) {
+        self.accumulator = 0.0;
     }
 
     pub fn clear_cache(&mut self) {
@@ -121,217 +1910,833 @@ impl RustyCalculator {
     pub fn recover_cache(&mut self) -> Result<(), CalculationError> {
         if let Some(snapshot) = self.snapshots.pop() {
             // Restore complete calculator state from snapshot
-            self.root = snapshot.root;
-            self.current = snapshot.current;
-            self.history = snapshot.history;
-            self.history_index = snapshot.history_index;
+            self.restore(snapshot);
 
-            println!("Recovered to cached state with value: {}", self.current.borrow().value);
+            if self.verbose { println!("Recovered to cached state with value: {}", self.current_display()); }
             Ok(())
         } else {
-            Err(CalculationError::CannotDeleteRoot)
+            Err(CalculationError::NoSnapshotAvailable)
         }
     }
 
-    // Unified value validation - combines all boundary checks
-    fn checked_value(_prev: f64, val: f64) -> Result<f64, CalculationError> {
-        if !val.is_finite() {
-            return Err(CalculationError::OutOfBounds);
+    // Unified value validation - combines all boundary checks.
+    // Magnitude is checked before digits so huge or infinite results report Overflow.
+    fn checked_value(&self, _prev: f64, val: f64) -> Result<f64, CalculationError> {
+        if val.is_nan() {
+            return Err(CalculationError::NotANumber);
+        }
+        if val.is_infinite() || val.abs() > f64::MAX / 2.0 {
+            return Err(CalculationError::Overflow);
         }
         let digits = if val.abs() > 0.0 { val.abs().log10().floor() as i32 } else { 0 };
-        if digits > 15 || val.abs() > f64::MAX / 2.0 {
+        if digits > self.max_significant_digits {
             return Err(CalculationError::PrecisionLoss);
         }
         Ok(val)
     }
 
     pub fn start() -> Result<RustyCalculator, CalculationError> {
-        println!("=== Rusty Calculator ===");
-        println!("Commands: 'start' to begin, 'help' for help, 'quit' to exit");
+        Self::start_with_verbosity(true)
+    }
+
+    // Same as start, but quiet sessions skip the banner and prompts
+    pub fn start_with_verbosity(verbose: bool) -> Result<RustyCalculator, CalculationError> {
+        if verbose {
+            println!("=== Rusty Calculator ===");
+            println!("Commands: 'start' to begin, 'help' for help, 'quit' to exit");
+        }
 
         loop {
-            print!("Enter command: ");
-            let input: String = get_input::<String>()?;
+            if verbose { print!("Enter command: "); }
+            let input: String = match get_input::<String>() {
+                Err(CalculationError::EndOfInput) => {
+                    if verbose { println!("Goodbye!"); }
+                    std::process::exit(0);
+                }
+                input => input?,
+            };
 
             match input.trim().to_lowercase().as_str() {
                 "help" => Self::print_help(),
-                "start" => {
-                    let mut calc = RustyCalculator::new(0.0);
-                    println!("Calculator started. Current value: {}", calc.current.borrow().value);
-                    Self::run_calculator_loop(&mut calc)?;
-                    return Ok(calc);
-                }
+                "start" => return Self::session(0.0, verbose),
+                command if command.starts_with("start ") => match parse_number(&command["start ".len()..]) {
+                    Ok(initial) => return Self::session(initial, verbose),
+                    Err(e) => println!("{}", error_text(&format!("{}. Try again.", e))),
+                },
                 "quit" | "exit" => {
-                    println!("Goodbye!");
+                    if verbose { println!("Goodbye!"); }
                     std::process::exit(0);
                 }
-                _ => println!("Unknown command: '{}'. Type 'help' for options.", input),
+                _ => println!("{}", error_text(&format!("Unknown command: '{}'. Type 'help' for options.", input))),
             }
         }
     }
 
-    // Centralized input handling for operations that require values
-    fn get_operation_value() -> Result<f64, CalculationError> {
-        println!("Enter value:");
-        get_input::<f64>()
+    // Begin an interactive session whose root holds the given value
+    pub fn start_with(initial: f64) -> Result<RustyCalculator, CalculationError> {
+        Self::session(initial, true)
     }
 
-    // Centralized error reporting for operations
-    fn handle_operation_result(result: Result<(), CalculationError>, operation: &str) {
-        if let Err(e) = result {
-            println!("{} failed: {}. State preserved.", operation, e);
-        }
+    fn session(initial: f64, verbose: bool) -> Result<RustyCalculator, CalculationError> {
+        let mut calc = RustyCalculator::new(initial);
+        calc.set_verbose(verbose);
+        if verbose { println!("Calculator started. Current value: {}", calc.current_display()); }
+        Self::run_calculator_loop(&mut calc)?;
+        Ok(calc)
     }
 
     fn run_calculator_loop(calc: &mut RustyCalculator) -> Result<(), CalculationError> {
+        let mut input = CommandInput::stdin();
+        let mut shown = calc.current;
         loop {
-            println!("\nCurrent value: {}", calc.current.borrow().value);
-            println!("Enter operation (1-14, 'help', or 'exit'):");
+            if calc.verbose {
+                println!("\n{}", calc.prompt_line());
+                input.prompt(calc.verbose, "Enter operation (1-19, 'help', or 'exit'):");
+            } else if calc.current != shown {
+                println!("{}", calc.current_display());
+            }
+            shown = calc.current;
 
-            let op_input: String = match get_input::<String>() {
-                Ok(v) => v,
-                Err(_) => { println!("Input error. Please try again."); continue; }
+            let line = match input.next_line() {
+                Ok(line) if line.is_empty() => { input.report("Input error. Please try again."); continue; }
+                Ok(line) => line,
+                Err(_) => break,
             };
-            let op_input = op_input.trim();
-
-            match op_input.to_lowercase().as_str() {
-                "help" => { Self::print_help(); continue; }
-                "exit" | "quit" => break,
-                _ => {}
+            if Self::run_command(calc, &line, &mut input).is_break() {
+                break;
             }
+        }
 
-            let op_num: i32 = match op_input.parse() {
-                Ok(v) => v,
-                Err(_) => { println!("Invalid command: '{}'. Use 1-14, 'help', or 'exit'", op_input); continue; }
-            };
+        if calc.verbose { println!("Calculator session ended."); }
+        Ok(())
+    }
+
+    // Run one REPL command line. A value typed after the command ("1 5", "goto 3") answers its
+    // first prompt; further prompts read the following lines. Break means the session should end.
+    fn run_command<I>(calc: &mut RustyCalculator, line: &str, input: &mut CommandInput<I>) -> ControlFlow<()>
+    where I: Iterator<Item = (usize, String)> {
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        input.inline = Some(rest.trim()).filter(|rest| !rest.is_empty()).map(String::from);
+        let flow = Self::dispatch_command(calc, line, word, input);
+        // Anything left on the line is dropped rather than read as the next command
+        input.inline = None;
+        if input.ended { ControlFlow::Break(()) } else { flow }
+    }
 
-            match op_num {
-                // Operations requiring input values
-                1..=5 => {
-                    match Self::get_operation_value() {
-                        Ok(value) => {
-                            let result = match op_num {
-                                1 => calc.add(value),
-                                2 => calc.subtract(value),
-                                3 => calc.multiply(value),
-                                4 => calc.divide(value),
-                                5 => calc.exp(value),
-                                _ => unreachable!(),
-                            };
-                            let op_name = match op_num {
-                                1 => "Addition",
-                                2 => "Subtraction",
-                                3 => "Multiplication",
-                                4 => "Division",
-                                5 => "Exponentiation",
-                                _ => unreachable!(),
-                            };
-                            Self::handle_operation_result(result, op_name);
+    fn dispatch_command<I>(calc: &mut RustyCalculator, line: &str, word: &str, input: &mut CommandInput<I>) -> ControlFlow<()>
+    where I: Iterator<Item = (usize, String)> {
+        match word.to_lowercase().as_str() {
+            "help" => Self::print_help(),
+            "exit" | "quit" => return ControlFlow::Break(()),
+            #[cfg(feature = "serde")]
+            "save" => {
+                input.prompt(calc.verbose, "Enter filename:");
+                match input.read::<String>() {
+                    Ok(name) => match calc.save_to_file(Path::new(&name)) {
+                        Ok(()) => println!("Session saved to {}.", name),
+                        Err(e) => input.report(&format!("Save failed: {}.", e)),
+                    },
+                    Err(_) => input.report("Invalid filename. Try again."),
+                }
+            }
+            #[cfg(feature = "serde")]
+            "load" => {
+                input.prompt(calc.verbose, "Enter filename:");
+                match input.read::<String>() {
+                    Ok(name) => match RustyCalculator::load_from_file(Path::new(&name)) {
+                        Ok(mut loaded) => {
+                            loaded.copy_settings(calc);
+                            *calc = loaded;
+                            println!("Session loaded from {}.", name);
                         }
-                        Err(_) => { println!("Invalid number. Try again."); continue; }
+                        Err(e) => input.report(&format!("Load failed: {}. State preserved.", e)),
+                    },
+                    Err(_) => input.report("Invalid filename. Try again."),
+                }
+            }
+            "mode" => {
+                let next = match calc.calc_mode {
+                    CalcMode::Real => CalcMode::Integer,
+                    #[cfg(not(feature = "decimal"))]
+                    CalcMode::Integer => CalcMode::Real,
+                    #[cfg(feature = "decimal")]
+                    CalcMode::Integer => CalcMode::Decimal,
+                    #[cfg(feature = "decimal")]
+                    CalcMode::Decimal => CalcMode::Real,
+                };
+                calc.set_calc_mode(next);
+                println!("Calculation mode: {:?}", next);
+            }
+            "repeat" => input.handle(calc.repeat_last(), "Repeat"),
+            "replay-branch" => input.handle(calc.replay_as_branch(), "Replay as branch"),
+            "=" => input.handle(calc.commit(), "Commit"),
+            "entry" => {
+                let next = match calc.entry_mode {
+                    EntryMode::Chain => EntryMode::Immediate,
+                    EntryMode::Immediate => EntryMode::Chain,
+                };
+                calc.set_entry_mode(next);
+                println!("Entry mode: {:?}", next);
+            }
+            "angle" => {
+                let next = match calc.angle_mode {
+                    AngleMode::Radians => AngleMode::Degrees,
+                    AngleMode::Degrees => AngleMode::Radians,
+                };
+                calc.set_angle_mode(next);
+                println!("Angle mode: {:?}", next);
+            }
+            "format" => {
+                let next = match calc.output_format {
+                    OutputFormat::Decimal => OutputFormat::Scientific,
+                    OutputFormat::Scientific => OutputFormat::Engineering,
+                    OutputFormat::Engineering => OutputFormat::Decimal,
+                };
+                calc.set_output_format(next);
+                println!("Output format: {:?}", next);
+            }
+            "round" => input.handle(calc.round(), "Round"),
+            "rounding" => {
+                let next = match calc.rounding_mode {
+                    RoundingMode::HalfEven => RoundingMode::HalfUp,
+                    RoundingMode::HalfUp => RoundingMode::TowardZero,
+                    RoundingMode::TowardZero => RoundingMode::AwayFromZero,
+                    RoundingMode::AwayFromZero => RoundingMode::HalfEven,
+                };
+                calc.set_rounding_mode(next);
+                println!("Rounding mode: {:?}", next);
+            }
+            "group" => {
+                calc.set_group_digits(!calc.group_digits);
+                println!("Digit grouping {}", if calc.group_digits { "on" } else { "off" });
+            }
+            "fractions" => {
+                calc.set_fraction_display(!calc.fraction_display);
+                println!("Fraction display {}", if calc.fraction_display { "on" } else { "off" });
+            }
+            "hex" | "bin" => {
+                let radix = if word.eq_ignore_ascii_case("hex") { 16 } else { 2 };
+                match calc.show_radix(radix) {
+                    Ok(rendered) => println!("{}", rendered),
+                    Err(e) => input.report(&format!("Radix display failed: {}. Value must be a whole number.", e)),
+                }
+            }
+            "temp" => {
+                input.prompt(calc.verbose, "Convert from (C/F/K):");
+                let from = input.read::<String>();
+                input.prompt(calc.verbose, "Convert to (C/F/K):");
+                let to = input.read::<String>();
+                match from.and_then(|from| Ok((from, to?))) {
+                    Ok((from, to)) => input.handle(calc.convert_temperature(&from, &to), "Temperature conversion"),
+                    Err(_) => input.report("Invalid unit. Try again."),
+                }
+            }
+            "sum" => {
+                let result = input.read_values(calc.verbose).and_then(|values| calc.sum_of(&values));
+                input.handle(result, "Sum");
+            }
+            "mean" => {
+                let result = input.read_values(calc.verbose).and_then(|values| calc.mean_of(&values));
+                input.handle(result, "Mean");
+            }
+            "stddev" => {
+                let result = input.read_values(calc.verbose).and_then(|values| calc.stddev_of(&values));
+                input.handle(result, "Standard deviation");
+            }
+            "compound" => {
+                input.prompt(calc.verbose, "Rate per period (0.05 = 5%):");
+                let rate = input.read::<f64>();
+                input.prompt(calc.verbose, "Number of periods:");
+                let periods = input.read::<f64>();
+                match (rate, periods) {
+                    (Ok(rate), Ok(periods)) => input.handle(calc.compound_interest(rate, periods), "Compound interest"),
+                    _ => input.report("Invalid number. Try again."),
+                }
+            }
+            "convert" => {
+                input.prompt(calc.verbose, "Category (length/weight):");
+                let category = input.read::<String>();
+                input.prompt(calc.verbose, "Convert from:");
+                let from = input.read::<String>();
+                input.prompt(calc.verbose, "Convert to:");
+                let to = input.read::<String>();
+                match (category, from, to) {
+                    (Ok(category), Ok(from), Ok(to)) => input.handle(calc.convert_unit(&category, &from, &to), "Unit conversion"),
+                    _ => input.report("Invalid unit. Try again."),
+                }
+            }
+            "tetr" => {
+                input.prompt(calc.verbose, &format!("Tower height (0-{}):", TETRATION_MAX_HEIGHT));
+                match input.read::<u32>() {
+                    Ok(height) => input.handle(calc.tetrate(height), "Tetration"),
+                    Err(_) => input.report("Invalid height. Try again."),
+                }
+            }
+            "fraction" => {
+                match calc.as_fraction(FRACTION_MAX_DENOMINATOR) {
+                    Some((numerator, denominator)) => println!("{}/{}", numerator, denominator),
+                    None => println!("No fraction for {}", calc.current_display()),
+                }
+            }
+            "words" => {
+                match calc.to_words() {
+                    Ok(words) => println!("{}", words),
+                    Err(e) => input.report(&format!("Words failed: {}.", e)),
+                }
+            }
+            "replay" => {
+                input.prompt(calc.verbose, "Enter filename:");
+                match input.read::<String>() {
+                    Ok(name) => input.handle(calc.replay_file(Path::new(&name)), "Replay"),
+                    Err(_) => input.report("Invalid filename. Try again."),
+                }
+            }
+            "ms" | "mr" => {
+                input.prompt(calc.verbose, &format!("Enter memory slot (0-{}):", MEMORY_SLOTS - 1));
+                match input.read::<usize>() {
+                    Ok(slot) if word.eq_ignore_ascii_case("ms") => input.handle(calc.memory_store(slot), "Memory store"),
+                    Ok(slot) => input.handle(calc.memory_recall(slot), "Memory recall"),
+                    Err(_) => input.report("Invalid slot. Try again."),
+                }
+            }
+            "ans" => {
+                input.prompt(calc.verbose, "Steps back:");
+                match input.read::<usize>() {
+                    Ok(back) => input.handle(calc.recall_offset(back), "Recall"),
+                    Err(_) => input.report("Invalid step count. Try again."),
+                }
+            }
+            "mx" => {
+                calc.memory_swap();
+                println!("Swapped with memory. Current value: {}", calc.current_display());
+            }
+            "mc" => {
+                calc.memory_clear_all();
+                println!("All memory slots cleared.");
+            }
+            "snapshots" => {
+                let snapshots = calc.list_snapshots();
+                if snapshots.is_empty() { println!("No snapshots saved."); }
+                for (index, value) in snapshots {
+                    println!("[{}] {}", index, calc.format_value(value));
+                }
+            }
+            "snap-save" | "snap-restore" => {
+                input.prompt(calc.verbose, "Enter snapshot name:");
+                match input.read::<String>() {
+                    Ok(name) if word.eq_ignore_ascii_case("snap-save") => {
+                        calc.snapshot_named(&name);
+                        println!("Saved snapshot '{}'.", name);
                     }
+                    Ok(name) => input.handle(calc.recover_named(&name), "Snapshot restore"),
+                    Err(_) => input.report("Invalid name. Try again."),
                 }
-                // Single-value operations
-                6 => Self::handle_operation_result(calc.square_root(), "Square root"),
-                7 => Self::handle_operation_result(calc.square(), "Square"),
-                8 => Self::handle_operation_result(calc.natural_log(), "Natural log"),
-                // Navigation operations
-                9 => Self::handle_operation_result(calc.go_forwards(), "Redo"),
-                10 => Self::handle_operation_result(calc.go_backwards(), "Undo"),
-                // Utility operations
-                11 => calc.reset(),
-                12 => calc.show_history(),
-                13 => Self::handle_operation_result(calc.recover_cache(), "Cache recovery"),
-                14 => break,
-                _ => println!("Invalid option: {}. Use 1-14.", op_num),
-            }
-        }
-
-        println!("Calculator session ended.");
-        Ok(())
+            }
+            "hard-reset" => {
+                calc.hard_reset();
+                println!("Calculator reset to 0. History and snapshots discarded.");
+            }
+            "info" => println!("{}", calc.status_string()),
+            "acc" => {
+                calc.accumulate();
+                println!("Accumulator: {}", calc.format_value(calc.accumulator()));
+            }
+            "acc-show" => println!("Accumulator: {}", calc.format_value(calc.accumulator())),
+            "acc-clear" => {
+                calc.clear_accumulator();
+                println!("Accumulator cleared.");
+            }
+            "store" | "recall" => {
+                input.prompt(calc.verbose, "Enter variable name:");
+                match input.read::<String>() {
+                    Ok(name) if word.eq_ignore_ascii_case("store") => {
+                        calc.store_var(&name);
+                        println!("Stored {} as '{}'", calc.current_display(), name);
+                    }
+                    Ok(name) => input.handle(calc.recall_var(&name), "Recall"),
+                    Err(_) => input.report("Invalid name. Try again."),
+                }
+            }
+            "transcript" => {
+                for (i, entry) in calc.transcript().iter().enumerate() {
+                    println!("{:>3}: {}", i + 1, entry);
+                }
+            }
+            "bfs" => calc.show_history_bfs(),
+            "clear-history" => {
+                calc.clear_history();
+                println!("Undo history cleared. Current value: {}", calc.current_display());
+            }
+            "compress" => {
+                calc.compress_chain();
+                println!("History compressed. Current value: {}", calc.current_display());
+            }
+            "collapse" => {
+                calc.collapse_to_value();
+                println!("History collapsed to {}. Previous tree saved to snapshots.", calc.current_display());
+            }
+            "dot" => print!("{}", calc.to_dot()),
+            "goto" => {
+                calc.show_history();
+                calc.print_indexed_history();
+                input.prompt(calc.verbose, "Enter history index:");
+                match input.read::<usize>() {
+                    Ok(index) => input.handle(calc.goto_history(index), "Jump"),
+                    Err(_) => input.report("Invalid index. Try again."),
+                }
+            }
+            "delete-at" => {
+                calc.print_indexed_history();
+                input.prompt(calc.verbose, "Enter history index to delete:");
+                match input.read::<usize>() {
+                    Ok(index) => input.handle(calc.delete_at(index), "Delete"),
+                    Err(_) => input.report("Invalid index. Try again."),
+                }
+            }
+            "branch" => {
+                for (i, value) in calc.child_values().iter().enumerate() {
+                    println!("[{}] {}", i, calc.format_value(*value));
+                }
+                input.prompt(calc.verbose, "Enter branch index:");
+                match input.read::<usize>() {
+                    Ok(branch) => input.handle(calc.redo_branch(branch), "Branch redo"),
+                    Err(_) => input.report("Invalid index. Try again."),
+                }
+            }
+            "undo-until" => {
+                match input.read_value(calc.verbose) {
+                    Ok(target) => input.handle(calc.undo_until(target, UNDO_UNTIL_EPSILON), "Undo until"),
+                    Err(e) => input.report(&format!("{}. Try again.", e)),
+                }
+            }
+            _ => return Self::run_numbered(calc, line, word, input),
+        }
+        ControlFlow::Continue(())
+    }
+
+    // Menu numbers, falling back to shorthand such as "+ 5" or "sqrt"
+    fn run_numbered<I>(calc: &mut RustyCalculator, line: &str, word: &str, input: &mut CommandInput<I>) -> ControlFlow<()>
+    where I: Iterator<Item = (usize, String)> {
+        // Only bare digits are menu numbers, so "+5" and "-3" stay shorthand
+        let op_num: i32 = match word.parse() {
+            Ok(v) if word.chars().all(|c| c.is_ascii_digit()) => v,
+            _ => {
+                input.inline = None;
+                match Self::parse_op(line) {
+                    Ok((label, Some(operand))) if calc.entry_mode == EntryMode::Immediate && ["+", "-", "*", "/", "^"].contains(&label.as_str()) => {
+                        let result = calc.set_pending(&label).and_then(|()| calc.enter_operand(operand));
+                        input.handle(result, "Pending operation");
+                    }
+                    Ok((label, operand)) => input.handle(calc.apply_labelled(&label, operand, None), "Operation"),
+                    // An operator with a bad operand gets the operand's error, not "invalid command"
+                    Err(e) if line.starts_with(['+', '-', '*', '/', '^']) => input.report(&format!("{}. Try again.", e)),
+                    Err(_) => input.report(&format!("Invalid command: '{}'. Use 1-19, 'help', or 'exit'", line)),
+                }
+                return ControlFlow::Continue(());
+            }
+        };
+
+        match op_num {
+            // Operations requiring input values
+            // Immediate mode queues binary operations instead of applying them
+            1..=5 if calc.entry_mode == EntryMode::Immediate => {
+                let op = ["+", "-", "*", "/", "^"][op_num as usize - 1];
+                let result = calc.set_pending(op).and_then(|()| {
+                    let value = input.read_value(calc.verbose)?;
+                    calc.enter_operand(value)
+                });
+                input.handle(result, "Pending operation");
+            }
+            1..=5 | 15..=19 => {
+                match input.read_value(calc.verbose) {
+                    Ok(value) => {
+                        let result = match op_num {
+                            1 => calc.add(value),
+                            2 => calc.subtract(value),
+                            3 => calc.multiply(value),
+                            4 => calc.divide(value),
+                            5 => calc.exp(value),
+                            15 => calc.atan2(value),
+                            16 => calc.hypot(value),
+                            17 => calc.equals(value),
+                            18 => calc.less_than(value),
+                            19 => calc.greater_than(value),
+                            _ => unreachable!(),
+                        };
+                        let op_name = match op_num {
+                            1 => "Addition",
+                            2 => "Subtraction",
+                            3 => "Multiplication",
+                            4 => "Division",
+                            5 => "Exponentiation",
+                            15 => "Arctangent",
+                            16 => "Hypotenuse",
+                            17 => "Equality test",
+                            18 => "Less-than test",
+                            19 => "Greater-than test",
+                            _ => unreachable!(),
+                        };
+                        input.handle(result, op_name);
+                    }
+                    Err(e) => input.report(&format!("{}. Try again.", e)),
+                }
+            }
+            // Single-value operations
+            6 => input.handle(calc.square_root(), "Square root"),
+            7 => input.handle(calc.square(), "Square"),
+            8 => input.handle(calc.natural_log(), "Natural log"),
+            // Navigation operations
+            9 => input.handle(calc.go_forwards(), "Redo"),
+            10 => input.handle(calc.go_backwards(), "Undo"),
+            // Utility operations
+            11 => calc.reset(),
+            12 => calc.show_history(),
+            13 => input.handle(calc.recover_cache(), "Cache recovery"),
+            14 => return ControlFlow::Break(()),
+            _ => input.report(&format!("Invalid option: {}. Use 1-19.", op_num)),
+        }
+        ControlFlow::Continue(())
     }
 
     fn print_help() {
         println!("\n=== Calculator Help ===");
         let startup_cmds: &[(&str, &str)] = &[
             ("start", "Start the calculator"),
+            ("start N", "Start the calculator at value N"),
             ("help", "Show this help"),
             ("quit", "Exit program"),
         ];
+        #[cfg(feature = "serde")]
+        let session_cmds: &[(&str, &str)] = &[("save", "Save session to a file"), ("load", "Load session from a file")];
+        #[cfg(not(feature = "serde"))]
+        let session_cmds: &[(&str, &str)] = &[];
         let calc_ops: &[(&str, &str)] = &[("1", "Addition"), ("2", "Subtraction"), ("3", "Multiplication"), ("4", "Division"),
             ("5", "Exponentiation"), ("6", "Square root"), ("7", "Square"), ("8", "Natural logarithm"), ("9", "Redo (go forwards)"),
             ("10", "Undo (go backwards)"), ("11", "Reset"), ("12", "Show history"), ("13", "Recover from cache"), ("14", "Exit calculator"),
-            ("help", "Show operations help"),
+            ("15", "Arctangent of current (y) over x"), ("16", "Hypotenuse of current and x"),
+            ("17", "1 if current equals x, else 0"), ("18", "1 if current < x, else 0"), ("19", "1 if current > x, else 0"),
+            ("undo-until", "Undo back to a given value"), ("goto", "Jump to a history index"),
+            ("branch", "Redo into a chosen child branch"), ("delete-at", "Remove a history entry by index"),
+            ("collapse", "Keep current value, discard history"), ("compress", "Merge repeated operations in history"),
+            ("clear-history", "Start undo/redo afresh from here"), ("bfs", "Show history level by level"),
+            ("transcript", "List every attempted operation in order"),
+            ("dot", "Print history tree as Graphviz DOT"),
+            ("mode", "Cycle real/integer (/decimal) arithmetic"), ("angle", "Toggle radians/degrees"),
+            ("entry", "Toggle chain/immediate entry"), ("=", "Apply the pending operation"),
+            ("repeat", "Apply the last operation again"), ("format", "Cycle decimal/scientific/engineering output"),
+            ("group", "Toggle thousands separators"), ("fractions", "Toggle showing values as fractions too"),
+            ("replay-branch", "Re-apply the history path from the current value"),
+            ("round", "Round to a whole number"), ("rounding", "Cycle the rounding mode"),
+            ("words", "Spell out the current value"), ("fraction", "Show current value as a fraction"),
+            ("temp", "Convert a temperature between C, F and K"), ("convert", "Convert a length or weight"),
+            ("compound", "Apply compound interest to the current value"), ("stddev", "Standard deviation of entered values"),
+            ("sum", "Sum of entered values"), ("mean", "Mean of entered values"),
+            ("tetr", "Raise the current value to itself repeatedly (power tower)"), ("hex", "Show current value in hexadecimal"),
+            ("bin", "Show current value in binary"), ("store", "Save current value under a name"),
+            ("recall", "Insert a stored value"), ("replay", "Apply operations listed in a file"),
+            ("info", "Summarise calculator state"),
+            ("hard-reset", "Reset and discard all history and snapshots"),
+            ("snapshots", "List saved snapshots"), ("snap-save", "Save a named snapshot"),
+            ("snap-restore", "Restore a named snapshot"), ("ms", "Store current value in a memory slot"),
+            ("mr", "Recall a memory slot"), ("mc", "Clear all memory slots"),
+            ("mx", "Swap current value with memory slot 0"), ("ans", "Reuse the value from N steps back"),
+            ("acc", "Add current value to the accumulator"), ("acc-show", "Show the accumulator"),
+            ("acc-clear", "Reset the accumulator"), ("help", "Show operations help"),
         ];
         let sections: &[(&str, &[(&str, &str)])] = &[
             ("Startup commands", startup_cmds),
             ("Calculator operations", calc_ops),
+            ("Session commands", session_cmds),
         ];
-        for (title, commands) in sections {
+        // One column width for every section, wide enough for the longest command name
+        let width = sections.iter().flat_map(|(_, commands)| commands.iter())
+            .map(|(cmd, _)| cmd.chars().count()).max().unwrap_or(0);
+        for (title, commands) in sections.iter().filter(|(_, commands)| !commands.is_empty()) {
             println!("{}:", title);
             for (cmd, desc) in *commands {
-                println!("  {:<5} - {}", cmd, desc);
+                println!("  {:<width$} - {}", cmd, desc, width = width);
             }
             println!();
         }
     }
 }
 
+// Fluent wrappers around LogicOperations, e.g. calc.chain_add(2.0)?.chain_multiply(3.0)?
+impl RustyCalculator {
+    pub fn chain_add(&mut self, value: f64) -> Result<&mut Self, CalculationError> {
+        self.add(value)?;
+        Ok(self)
+    }
+    pub fn chain_subtract(&mut self, value: f64) -> Result<&mut Self, CalculationError> {
+        self.subtract(value)?;
+        Ok(self)
+    }
+    pub fn chain_multiply(&mut self, value: f64) -> Result<&mut Self, CalculationError> {
+        self.multiply(value)?;
+        Ok(self)
+    }
+    pub fn chain_divide(&mut self, value: f64) -> Result<&mut Self, CalculationError> {
+        self.divide(value)?;
+        Ok(self)
+    }
+    pub fn chain_exp(&mut self, value: f64) -> Result<&mut Self, CalculationError> {
+        self.exp(value)?;
+        Ok(self)
+    }
+    pub fn chain_square(&mut self) -> Result<&mut Self, CalculationError> {
+        self.square()?;
+        Ok(self)
+    }
+    pub fn chain_square_root(&mut self) -> Result<&mut Self, CalculationError> {
+        self.square_root()?;
+        Ok(self)
+    }
+    pub fn chain_natural_log(&mut self) -> Result<&mut Self, CalculationError> {
+        self.natural_log()?;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl RustyCalculator {
+    // Serialize the reachable tree, current position and history path.
+    // History entries that were deleted from the tree are dropped.
+    pub fn to_json(&self) -> Result<String, CalculationError> {
+        let order = self.tree_nodes();
+        let mut positions = vec![None; self.nodes.len()];
+        for (position, &node) in order.iter().enumerate() {
+            positions[node] = Some(position);
+        }
+        let nodes = order.iter().map(|&node| {
+            let n = &self.nodes[node];
+            SerializedNode {
+                value: n.value,
+                last_op: n.last_op.clone(),
+                operand: n.operand,
+                extra: n.extra,
+                parent: n.parent.and_then(|p| positions[p]),
+            }
+        }).collect();
+
+        let reachable_before = |end: usize| self.history[..end].iter().filter(|&&i| positions[i].is_some()).count();
+        let session = SerializedSession {
+            nodes,
+            current: positions[self.current].unwrap_or(0),
+            history: self.history.iter().filter_map(|&i| positions[i]).collect(),
+            history_index: reachable_before(self.history_index + 1).saturating_sub(1),
+        };
+        serde_json::to_string(&session).map_err(|e| CalculationError::ParseError(format!("JSON error: {}", e)))
+    }
+
+    pub fn from_json(json: &str) -> Result<RustyCalculator, CalculationError> {
+        let session: SerializedSession = serde_json::from_str(json)
+            .map_err(|e| CalculationError::ParseError(format!("JSON error: {}", e)))?;
+
+        let mut calc = RustyCalculator::new(0.0);
+        calc.nodes.clear();
+        for (position, node) in session.nodes.into_iter().enumerate() {
+            // Only the first node may be the root, and every parent must come earlier in pre-order
+            if (position == 0) != node.parent.is_none() || node.parent.is_some_and(|p| p >= position) {
+                return Err(CalculationError::ParseError("Session nodes are not a single tree in pre-order".to_string()));
+            }
+            let index = calc.push_node(Node::new(node.value, node.parent, node.last_op, node.operand));
+            calc.nodes[index].extra = node.extra;
+            if let Some(parent) = node.parent {
+                calc.nodes[parent].child_item.push(index);
+            }
+        }
+
+        let node_count = calc.nodes.len();
+        if node_count == 0 || session.current >= node_count || session.history_index >= session.history.len()
+            || session.history.iter().any(|&i| i >= node_count) {
+            return Err(CalculationError::ParseError("Session refers to nodes outside the tree".to_string()));
+        }
+        calc.current = session.current;
+        calc.history = Rc::new(session.history);
+        calc.history_index = session.history_index;
+        // Running statistics describe the loaded tree, as if its values had been inserted this session
+        calc.running_stats = RunningStats::default();
+        for node in &calc.nodes {
+            calc.running_stats.push(node.value);
+        }
+        Ok(calc)
+    }
+
+    // Carry display, arithmetic and REPL settings over from another calculator; sessions
+    // loaded from file only hold the tree
+    fn copy_settings(&mut self, from: &RustyCalculator) {
+        self.output_format = from.output_format;
+        self.group_digits = from.group_digits;
+        self.fraction_display = from.fraction_display;
+        self.calc_mode = from.calc_mode;
+        self.max_significant_digits = from.max_significant_digits;
+        self.round_display_epsilon = from.round_display_epsilon;
+        self.angle_mode = from.angle_mode;
+        self.rounding_mode = from.rounding_mode;
+        self.equals_epsilon = from.equals_epsilon;
+        self.entry_mode = from.entry_mode;
+        self.verbose = from.verbose;
+        self.set_max_history(from.max_history);
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<(), CalculationError> {
+        std::fs::write(path, self.to_json()?)
+            .map_err(|e| CalculationError::ParseError(format!("IO error writing {}: {}", path.display(), e)))
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<RustyCalculator, CalculationError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| CalculationError::ParseError(format!("IO error reading {}: {}", path.display(), e)))?;
+        RustyCalculator::from_json(&json)
+    }
+}
+
 impl LogicOperations for RustyCalculator {
     fn add(&mut self, val: f64) -> Result<(), CalculationError> {
-        self.apply_op(|prev| prev + val, "+")
+        self.apply_op(|prev| prev + val, "+", Some(val))
     }
     fn subtract(&mut self, val: f64) -> Result<(), CalculationError> {
-        self.apply_op(|prev| prev - val, "-")
+        self.apply_op(|prev| prev - val, "-", Some(val))
     }
     fn multiply(&mut self, val: f64) -> Result<(), CalculationError> {
-        self.apply_op(|prev| prev * val, "*")
+        self.apply_op(|prev| prev * val, "*", Some(val))
     }
     fn divide(&mut self, val: f64) -> Result<(), CalculationError> {
-        if val == 0.0 { return Err(CalculationError::DivisionByZero); }
-        self.apply_op(|prev| prev / val, "/")
+        // A NaN current value is reported as such rather than as a division error
+        if self.nodes[self.current].value.is_nan() { return self.reject("/", Some(val), CalculationError::NotANumber); }
+        if val == 0.0 { return self.reject("/", Some(val), CalculationError::DivisionByZero); }
+        if self.calc_mode == CalcMode::Integer {
+            return self.apply_op(|prev| (prev / val).floor(), "/", Some(val));
+        }
+        self.apply_op(|prev| prev / val, "/", Some(val))
     }
     fn exp(&mut self, val: f64) -> Result<(), CalculationError> {
-        self.apply_op(|prev| prev.powf(val), "^")
+        self.apply_op(|prev| prev.powf(val), "^", Some(val))
     }
     fn square(&mut self) -> Result<(), CalculationError> {
-        self.apply_op(|prev| prev * prev, "sqr")
+        self.apply_op(|prev| prev * prev, "sqr", None)
     }
     fn square_root(&mut self) -> Result<(), CalculationError> {
-        if self.current.borrow().value < 0.0 { return Err(CalculationError::OutOfBounds); }
-        self.apply_op(|prev| prev.sqrt(), "√")
+        let prev = self.nodes[self.current].value;
+        if prev < 0.0 { return self.reject("√", None, CalculationError::domain("square_root", prev)); }
+        self.apply_op(|prev| prev.sqrt(), "√", None)
     }
     fn natural_log(&mut self) -> Result<(), CalculationError> {
-        if self.current.borrow().value <= 0.0 { return Err(CalculationError::OutOfBounds); }
-        self.apply_op(|prev| prev.ln(), "ln")
+        let prev = self.nodes[self.current].value;
+        if prev <= 0.0 { return self.reject("ln", None, CalculationError::domain("natural_log", prev)); }
+        self.apply_op(|prev| prev.ln(), "ln", None)
+    }
+    fn atan2(&mut self, x: f64) -> Result<(), CalculationError> {
+        let degrees = self.angle_mode == AngleMode::Degrees;
+        self.apply_op(|y| if degrees { y.atan2(x).to_degrees() } else { y.atan2(x) }, "atan2", Some(x))
+    }
+    // f64::hypot scales internally, so large legs don't overflow the way sqrt(a² + b²) would
+    fn hypot(&mut self, other: f64) -> Result<(), CalculationError> {
+        self.apply_op(|prev| prev.hypot(other), "hypot", Some(other))
+    }
+    // Done in u64 because log2().ceil() misjudges values just above large powers of two
+    fn next_power_of_two(&mut self) -> Result<(), CalculationError> {
+        let prev = self.nodes[self.current].value;
+        let power = (prev >= 1.0 && prev.fract() == 0.0 && prev <= u64::MAX as f64)
+            .then(|| (prev as u64).checked_next_power_of_two())
+            .flatten();
+        match power {
+            Some(power) => self.apply_op(|_| power as f64, "np2", None),
+            None => self.reject("np2", None, CalculationError::OutOfBounds),
+        }
+    }
+    // The comparisons replace the current value with 1.0 (true) or 0.0 (false)
+    fn equals(&mut self, other: f64) -> Result<(), CalculationError> {
+        let epsilon = self.equals_epsilon;
+        self.apply_op(|prev| if (prev - other).abs() <= epsilon { 1.0 } else { 0.0 }, "==", Some(other))
+    }
+    fn less_than(&mut self, other: f64) -> Result<(), CalculationError> {
+        self.apply_op(|prev| if prev < other { 1.0 } else { 0.0 }, "<", Some(other))
+    }
+    fn greater_than(&mut self, other: f64) -> Result<(), CalculationError> {
+        self.apply_op(|prev| if prev > other { 1.0 } else { 0.0 }, ">", Some(other))
+    }
+    fn nearest_power_of_two(&mut self) -> Result<(), CalculationError> {
+        let prev = self.nodes[self.current].value;
+        if prev <= 0.0 { return self.reject("≈2ⁿ", None, CalculationError::domain("nearest_power_of_two", prev)); }
+        // Ties go to the smaller power
+        self.apply_op(|prev| {
+            let lower = prev.log2().floor().exp2();
+            let upper = lower * 2.0;
+            if upper - prev < prev - lower { upper } else { lower }
+        }, "≈2ⁿ", None)
+    }
+}
+
+impl RustyCalculator {
+    // Whole number within i64 range, as required by the bitwise operations
+    fn whole_i64(value: f64) -> Result<i64, CalculationError> {
+        if value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64 {
+            Ok(value as i64)
+        } else {
+            Err(CalculationError::OutOfBounds)
+        }
+    }
+
+    // Combine the current value and an operand as i64s and insert the result
+    fn apply_bitwise<F>(&mut self, value: f64, op_fn: F, op_label: &str) -> Result<(), CalculationError>
+    where F: FnOnce(i64, i64) -> Option<i64>, {
+        let result = RustyCalculator::whole_i64(self.nodes[self.current].value)
+            .and_then(|lhs| Ok((lhs, RustyCalculator::whole_i64(value)?)))
+            .and_then(|(lhs, rhs)| op_fn(lhs, rhs).ok_or(CalculationError::OutOfBounds));
+        match result {
+            Ok(result) => self.apply_op(|_| result as f64, op_label, Some(value)),
+            Err(e) => self.reject(op_label, Some(value), e),
+        }
+    }
+}
+
+impl BitwiseOperations for RustyCalculator {
+    fn bit_and(&mut self, value: f64) -> Result<(), CalculationError> {
+        self.apply_bitwise(value, |a, b| Some(a & b), "&")
+    }
+    fn bit_or(&mut self, value: f64) -> Result<(), CalculationError> {
+        self.apply_bitwise(value, |a, b| Some(a | b), "|")
+    }
+    fn bit_xor(&mut self, value: f64) -> Result<(), CalculationError> {
+        self.apply_bitwise(value, |a, b| Some(a ^ b), "^^")
+    }
+    fn shift_left(&mut self, value: f64) -> Result<(), CalculationError> {
+        // Reject shifts that push set bits out of the i64
+        self.apply_bitwise(value, |a, n| {
+            let shifted = a.checked_shl(u32::try_from(n).ok()?)?;
+            (shifted >> n == a).then_some(shifted)
+        }, "<<")
+    }
+    fn shift_right(&mut self, value: f64) -> Result<(), CalculationError> {
+        self.apply_bitwise(value, |a, n| a.checked_shr(u32::try_from(n).ok()?), ">>")
     }
 }
 
 impl GeneralOperations for RustyCalculator {
     fn input(&mut self, val: f64) {
         // For direct input, no operation associated
-        self.insert_node(val, None);
+        self.insert_node(val, None, None);
+        self.log_operation("input", None, &val.to_string());
     }
 
     fn output(&self) {
-        println!("{}", self.current.borrow().value);
+        println!("{}", self.current_display());
     }
 
     fn delete(&mut self) -> Result<(), CalculationError> {
-        let current_node = Rc::clone(&self.current);
-        if let Some(parent_weak) = &current_node.borrow().parent {
-            if let Some(parent_rc) = parent_weak.upgrade() {
-                parent_rc.borrow_mut().child_item.retain(|child| !Rc::ptr_eq(child, &current_node));
-                self.current = parent_rc;
-                Ok(())
-            } else { Err(CalculationError::CannotDeleteRoot) }
-        } else { Err(CalculationError::CannotDeleteRoot) }
+        let current_node = self.current;
+        let parent = self.nodes[current_node].parent.ok_or(CalculationError::CannotDeleteRoot)?;
+        // The node stays in the arena (snapshots may still refer to it) but is unlinked from the tree
+        self.nodes[parent].child_item.retain(|&child| child != current_node);
+        self.current = parent;
+        Ok(())
     }
 
     fn go_backwards(&mut self) -> Result<(), CalculationError> {
         if self.history_index == 0 { return Err(CalculationError::CannotGoBackwards); }
         self.history_index -= 1;
-        self.current = Rc::clone(&self.history[self.history_index]);
+        self.current = self.history[self.history_index];
         Ok(())
     }
 
@@ -340,48 +2745,48 @@ impl GeneralOperations for RustyCalculator {
         if self.history_index + 1 >= self.history.len() {
             return Err(CalculationError::CannotGoForwards); }
         self.history_index += 1;
-        self.current = Rc::clone(&self.history[self.history_index]);
+        self.current = self.history[self.history_index];
         Ok(())
     }
 
     fn result(&self) -> f64 {
-        self.current.borrow().value
+        self.nodes[self.current].value
     }
 
     fn reset(&mut self) {
         self.snapshot();
-        let new_root = Node::new_root(0.0);
-        self.root = Rc::clone(&new_root);
-        self.current = Rc::clone(&new_root);
-        self.history.clear();
-        self.history.push(Rc::clone(&new_root));
+        let new_root = self.push_node(Node::new_root(0.0));
+        self.root = new_root;
+        self.current = new_root;
+        self.history = Rc::new(vec![new_root]);
         self.history_index = 0;
-        println!("Calculator reset to 0. Full history saved to snapshots.");
+        self.running_stats.push(0.0);
+        if self.verbose { println!("Calculator reset to 0. Full history saved to snapshots."); }
     }
 
     fn show_history(&self) {
-        fn traverse(node: &Rc<RefCell<Node>>, current: &Rc<RefCell<Node>>, prefix: String, is_last: bool) {
-            let n = node.borrow();
+        fn traverse(calc: &RustyCalculator, node: usize, prefix: String, is_last: bool) {
+            let n = &calc.nodes[node];
             print!("{}", prefix);
             print!("{}", if is_last { "└── " } else { "├── " });
-            print!("{}", n.value);
+            print!("{}", calc.format_value(n.value));
             if let Some(op) = &n.last_op {
                 print!(" | {}", op);
             }
             println!();
-            if Rc::ptr_eq(node, current) {
+            if node == calc.current {
                 println!("{}    ↑ (current)", prefix);
             }
 
             let new_prefix = if is_last { format!("{}    ", prefix) } else { format!("{}│   ", prefix) };
             let count = n.child_item.len();
-            for (i, child) in n.child_item.iter().enumerate() {
-                traverse(child, current, new_prefix.clone(), i == count - 1);
+            for (i, &child) in n.child_item.iter().enumerate() {
+                traverse(calc, child, new_prefix.clone(), i == count - 1);
             }
         }
 
         println!("--- Calculator History Tree ---");
-        traverse(&self.root, &self.current, "".to_string(), true);
+        traverse(self, self.root, "".to_string(), true);
     }
 }
 
@@ -392,11 +2797,18 @@ pub enum CalculationError {
     DivisionByZero,
     ParseError(String),            // Unified parsing error handling
     PrecisionLoss,
+    Overflow,                      // Magnitude too large, distinct from significant-digit loss
     CannotDeleteRoot,
     InvalidChildIndex,
     CannotGoBackwards,
     CannotGoForwards,              // Added missing forward navigation error
     OutOfBounds,
+    NotANumber,                    // Result was NaN (as opposed to infinite)
+    InsufficientData,              // Not enough history values for a statistic
+    NoSnapshotAvailable,
+    AtRoot,                        // Parent navigation attempted at the root node
+    DomainError(String),           // Input outside a function's mathematical domain, e.g. ln(-1)
+    EndOfInput,                    // Stdin closed (Ctrl-D); the REPL treats this as exit
 }
 impl std::fmt::Display for CalculationError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -404,15 +2816,29 @@ impl std::fmt::Display for CalculationError {
             CalculationError::DivisionByZero => write!(f, "Division by zero"),
             CalculationError::ParseError(msg) => write!(f, "Parse error: {}", msg),
             CalculationError::PrecisionLoss => write!(f, "Precision loss detected"),
+            CalculationError::Overflow => write!(f, "Overflow"),
             CalculationError::CannotDeleteRoot => write!(f, "Cannot delete root node"),
             CalculationError::InvalidChildIndex => write!(f, "Invalid child index"),
             CalculationError::CannotGoBackwards => write!(f, "Cannot go backwards"),
             CalculationError::CannotGoForwards => write!(f, "Cannot go forwards"),
             CalculationError::OutOfBounds => write!(f, "Value out of bounds"),
+            CalculationError::NotANumber => write!(f, "Result is not a number"),
+            CalculationError::InsufficientData => write!(f, "Not enough data"),
+            CalculationError::NoSnapshotAvailable => write!(f, "No snapshot available"),
+            CalculationError::AtRoot => write!(f, "Already at root node"),
+            CalculationError::DomainError(msg) => write!(f, "Domain error: {}", msg),
+            CalculationError::EndOfInput => write!(f, "End of input"),
         }
     }
 }
 
+impl CalculationError {
+    // DomainError naming the function and the rejected input
+    fn domain(function: &str, input: f64) -> Self {
+        CalculationError::DomainError(format!("{} is undefined for {}", function, input))
+    }
+}
+
 impl std::error::Error for CalculationError {}
 // Simplified From implementations - all parse errors go through ParseError(String)
 impl From<ParseFloatError> for CalculationError {
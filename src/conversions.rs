@@ -0,0 +1,52 @@
+use crate::calc::CalculationError;
+
+// Size of each unit in its category's base unit (metres, grams)
+const UNITS: &[(&str, &str, f64)] = &[
+    ("length", "m", 1.0),
+    ("length", "cm", 0.01),
+    ("length", "km", 1000.0),
+    ("length", "in", 0.0254),
+    ("length", "ft", 0.3048),
+    ("length", "mi", 1609.344),
+    ("weight", "g", 1.0),
+    ("weight", "kg", 1000.0),
+    ("weight", "lb", 453.59237),
+    ("weight", "oz", 28.349523125),
+];
+
+fn unknown_unit(unit: &str) -> CalculationError {
+    CalculationError::ParseError(format!("Unknown unit '{}'", unit))
+}
+
+// Convert a temperature between C, F and K (case-insensitive), going through Celsius
+pub fn temperature(value: f64, from: &str, to: &str) -> Result<f64, CalculationError> {
+    let celsius = match from.to_ascii_uppercase().as_str() {
+        "C" => value,
+        "F" => (value - 32.0) * 5.0 / 9.0,
+        "K" => value - 273.15,
+        _ => return Err(unknown_unit(from)),
+    };
+    match to.to_ascii_uppercase().as_str() {
+        "C" => Ok(celsius),
+        "F" => Ok(celsius * 9.0 / 5.0 + 32.0),
+        "K" => Ok(celsius + 273.15),
+        _ => Err(unknown_unit(to)),
+    }
+}
+
+// Category ("length" or "weight") that a unit belongs to
+pub fn unit_category(unit: &str) -> Option<&'static str> {
+    UNITS.iter().find(|(_, name, _)| name.eq_ignore_ascii_case(unit)).map(|&(category, _, _)| category)
+}
+
+// Convert a quantity between two units of the same category (case-insensitive)
+pub fn unit(value: f64, category: &str, from: &str, to: &str) -> Result<f64, CalculationError> {
+    if !UNITS.iter().any(|(c, _, _)| c.eq_ignore_ascii_case(category)) {
+        return Err(CalculationError::ParseError(format!("Unknown unit category '{}'", category)));
+    }
+    let factor = |unit: &str| UNITS.iter()
+        .find(|(c, name, _)| c.eq_ignore_ascii_case(category) && name.eq_ignore_ascii_case(unit))
+        .map(|&(_, _, factor)| factor)
+        .ok_or_else(|| unknown_unit(unit));
+    Ok(value * factor(from)? / factor(to)?)
+}
@@ -1,4 +1,6 @@
+pub mod bitwise_operations;
 pub mod calc;
+pub mod conversions;
 pub mod general_operations;
 pub mod logic_operations;
 
@@ -8,4 +8,11 @@ pub trait LogicOperations {
     fn square_root(&mut self)-> Result<(), CalculationError>;
     fn square(&mut self)-> Result<(), CalculationError>;
     fn natural_log(&mut self)-> Result<(), CalculationError>;
+    fn nearest_power_of_two(&mut self) -> Result<(), CalculationError>;
+    fn atan2(&mut self, x: f64) -> Result<(), CalculationError>;
+    fn hypot(&mut self, other: f64) -> Result<(), CalculationError>;
+    fn next_power_of_two(&mut self) -> Result<(), CalculationError>;
+    fn equals(&mut self, other: f64) -> Result<(), CalculationError>;
+    fn less_than(&mut self, other: f64) -> Result<(), CalculationError>;
+    fn greater_than(&mut self, other: f64) -> Result<(), CalculationError>;
 }
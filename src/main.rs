@@ -2,11 +2,35 @@ use rusty_calculator::calc::{CalculationError, RustyCalculator};
 
 
 fn main() -> Result<(), CalculationError> {
-    match RustyCalculator::start() {
-        Ok(_calc) => println!("Calculator finished successfully."),
+    // Any arguments form a single expression to evaluate without entering the REPL
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // --quiet keeps the REPL but drops banners and prompts, printing only results and errors
+    let verbose = args.iter().all(|arg| arg != "--quiet");
+    let args: Vec<String> = args.into_iter().filter(|arg| arg != "--quiet").collect();
+    // --stdin runs one operation per input line and prints the final value at EOF
+    if args == ["--stdin"] {
+        let mut calc = RustyCalculator::new(0.0);
+        calc.set_verbose(verbose);
+        calc.run_script(std::io::stdin().lock());
+        calc.show();
+        return Ok(());
+    }
+    if !args.is_empty() {
+        let mut calc = RustyCalculator::new(0.0);
+        match calc.eval_expression(&args.join(" ")) {
+            Ok(value) => println!("{}", calc.format_value(value)),
+            Err(e) => {
+                eprintln!("Calculator error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    match RustyCalculator::start_with_verbosity(verbose) {
+        Ok(_calc) if verbose => println!("Calculator finished successfully."),
+        Ok(_calc) => {}
         Err(e) => println!("Calculator error: {}", e),
     }
     Ok(())
 }
-
-